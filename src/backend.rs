@@ -0,0 +1,250 @@
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::task::JoinSet;
+
+use crate::error::{failure_reason, FetchError};
+use crate::fetcher::PageFetcher;
+use crate::resolver::ResolutionCache;
+use crate::steam_id::SteamId;
+use crate::steam_requester::{build_account_info, get_friends, resolve_steam_id, AccountInfo};
+
+pub type BackendFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, Box<dyn Error>>> + Send + 'a>>;
+
+/// Friends of an account as `(name, steam_id)` pairs, and the links of any whose SteamID could not
+/// be found, with the reason.
+#[derive(Debug, Clone, Default)]
+pub struct FriendList {
+    pub friends: Vec<(String, SteamId)>,
+    pub unresolved: Vec<(String, String)>,
+}
+
+impl FriendList {
+    /// Why some friends are missing from the list, to record against the account it belongs to.
+    pub fn failure(&self) -> Option<String> {
+        if self.unresolved.is_empty() {
+            return None;
+        }
+
+        let reasons = self
+            .unresolved
+            .iter()
+            .map(|(link, reason)| format!("{} ({})", link, reason))
+            .collect::<Vec<String>>();
+
+        Some(format!(
+            "Could not resolve {} friends: {}",
+            reasons.len(),
+            reasons.join(", ")
+        ))
+    }
+}
+
+/// Where account details and friend lists come from, independent of how they are fetched.
+pub trait SteamBackend: Send + Sync {
+    /// SteamID behind a profile link, including `/id/` vanity links.
+    fn resolve(&self, link: String) -> BackendFuture<'_, SteamId>;
+
+    fn account_info(&self, steam_id: SteamId) -> BackendFuture<'_, AccountInfo>;
+
+    /// Whether `account_summary` is much cheaper than `account_info`, so accounts are worth
+    /// ranking on their summaries before fetching only the best of them in full.
+    fn has_summaries(&self) -> bool {
+        false
+    }
+
+    /// Name, country and visibility of an account. Backends with nothing cheaper than the full
+    /// profile return that.
+    fn account_summary(&self, steam_id: SteamId) -> BackendFuture<'_, AccountInfo> {
+        self.account_info(steam_id)
+    }
+
+    fn friends(&self, steam_id: SteamId) -> BackendFuture<'_, FriendList>;
+}
+
+/// Scrapes the public steamcommunity.com profile and friends pages.
+pub struct HtmlBackend {
+    fetcher: Arc<dyn PageFetcher>,
+    resolved: Arc<ResolutionCache>,
+}
+
+/// Friend block position, name and vanity link, and the SteamID the link resolved to.
+type Lookup = (usize, String, String, Result<SteamId, String>);
+
+impl HtmlBackend {
+    pub fn new(fetcher: Arc<dyn PageFetcher>) -> Self {
+        HtmlBackend {
+            fetcher: fetcher,
+            resolved: Arc::new(ResolutionCache::new()),
+        }
+    }
+
+    async fn resolve_link(
+        resolved: &ResolutionCache,
+        fetcher: &dyn PageFetcher,
+        link: String,
+    ) -> Result<SteamId, Box<dyn Error>> {
+        resolved
+            .resolve(&link, |canonical| resolve_steam_id(fetcher, canonical))
+            .await
+    }
+
+    async fn resolved_friends(&self, steam_id: SteamId) -> Result<FriendList, Box<dyn Error>> {
+        let friends = get_friends(self.fetcher.as_ref(), steam_id.profile_url()).await?;
+
+        let mut resolved = Vec::new();
+        let mut lookups: JoinSet<Result<Lookup, FetchError>> = JoinSet::new();
+
+        for (index, (name, friend_id, link)) in friends.into_iter().enumerate() {
+            if let Some(friend_id) = friend_id {
+                resolved.push((index, name, friend_id));
+                continue;
+            }
+
+            let fetcher = Arc::clone(&self.fetcher);
+            let cache = Arc::clone(&self.resolved);
+
+            lookups.spawn(async move {
+                let friend_id =
+                    match Self::resolve_link(&cache, fetcher.as_ref(), link.clone()).await {
+                        Ok(friend_id) => Ok(friend_id),
+                        Err(err) => Err(failure_reason(err)?),
+                    };
+
+                Ok((index, name, link, friend_id))
+            });
+        }
+
+        let mut friend_list = FriendList::default();
+
+        for lookup in lookups.join_all().await {
+            let (index, name, link, friend_id) = lookup?;
+
+            match friend_id {
+                Ok(friend_id) => resolved.push((index, name, friend_id)),
+                Err(reason) => friend_list.unresolved.push((link, reason)),
+            }
+        }
+
+        // Lookups finish in any order, but the list keeps the order of the page.
+        resolved.sort_by_key(|(index, _, _)| *index);
+
+        friend_list.friends = resolved
+            .into_iter()
+            .map(|(_, name, friend_id)| (name, friend_id))
+            .collect();
+
+        Ok(friend_list)
+    }
+}
+
+impl SteamBackend for HtmlBackend {
+    fn resolve(&self, link: String) -> BackendFuture<'_, SteamId> {
+        Box::pin(Self::resolve_link(
+            &self.resolved,
+            self.fetcher.as_ref(),
+            link,
+        ))
+    }
+
+    fn account_info(&self, steam_id: SteamId) -> BackendFuture<'_, AccountInfo> {
+        Box::pin(build_account_info(
+            self.fetcher.as_ref(),
+            steam_id.profile_url(),
+        ))
+    }
+
+    fn friends(&self, steam_id: SteamId) -> BackendFuture<'_, FriendList> {
+        Box::pin(self.resolved_friends(steam_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::MemoryFetcher;
+    use crate::fixtures::{RecordingFetcher, ReplayFetcher};
+
+    const OWNER: u32 = 1;
+
+    const FRIENDS_PAGE: &str = r#"<html>
+<div class="selectable friend_block_v2 persona" data-steamid="76561197960265730"><a class="selectable_overlay" href="https://steamcommunity.com/profiles/76561197960265730"></a><div class="friend_block_content">P30<br><span>x</span></div></div>
+<div class="selectable friend_block_v2 persona"><a class="selectable_overlay" href="https://steamcommunity.com/id/found"></a><div class="friend_block_content">Found<br><span>x</span></div></div>
+<div class="selectable friend_block_v2 persona"><a class="selectable_overlay" href="https://steamcommunity.com/id/gone"></a><div class="friend_block_content">Gone<br><span>x</span></div></div>
+</html>"#;
+
+    const FOUND_PAGE: &str =
+        r#"<html><script>g_rgProfileData = {"steamid":"76561197960265740"};</script></html>"#;
+
+    fn owner_friends_url() -> String {
+        format!("{}/friends/", SteamId::from_account_id(OWNER).profile_url())
+    }
+
+    fn pages() -> MemoryFetcher {
+        let mut pages = MemoryFetcher::new();
+        pages.insert(owner_friends_url(), FRIENDS_PAGE.to_string());
+        pages.insert(
+            String::from("https://steamcommunity.com/id/found"),
+            FOUND_PAGE.to_string(),
+        );
+        pages
+    }
+
+    #[tokio::test]
+    async fn resolves_vanity_friends_in_page_order() {
+        let backend = HtmlBackend::new(Arc::new(pages()));
+
+        let friend_list = backend
+            .friends(SteamId::from_account_id(OWNER))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            friend_list.friends,
+            vec![
+                ("P30".to_string(), SteamId::from_account_id(2)),
+                ("Found".to_string(), SteamId::from_account_id(12)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unresolved_friends_are_reported() {
+        let backend = HtmlBackend::new(Arc::new(pages()));
+
+        let friend_list = backend
+            .friends(SteamId::from_account_id(OWNER))
+            .await
+            .unwrap();
+
+        assert_eq!(friend_list.unresolved.len(), 1);
+        assert_eq!(
+            friend_list.unresolved[0].0,
+            "https://steamcommunity.com/id/gone"
+        );
+        assert!(friend_list
+            .failure()
+            .unwrap()
+            .starts_with("Could not resolve 1 friends: https://steamcommunity.com/id/gone"));
+    }
+
+    #[tokio::test]
+    async fn fatal_lookup_errors_fail_the_list() {
+        let dir = std::env::temp_dir().join(format!("steam_backend_{}", std::process::id()));
+
+        // Only the friends page is recorded, so replaying the vanity lookups hits a missing
+        // fixture, which must not be mistaken for an unresolvable friend.
+        let recorder = RecordingFetcher::new(Arc::new(pages()), dir.clone()).unwrap();
+        recorder.fetch(owner_friends_url()).await.unwrap();
+
+        let backend = HtmlBackend::new(Arc::new(ReplayFetcher::new(dir.clone())));
+        let result = backend.friends(SteamId::from_account_id(OWNER)).await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let err = result.unwrap_err().downcast::<FetchError>().unwrap();
+        assert!(matches!(*err, FetchError::MissingFixture(_)));
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ScoringWeights, SearchConfig};
+use crate::error::CheckpointError;
+use crate::paths::FriendGraph;
+use crate::searcher::{Failures, SearchState, TargetResult};
+use crate::steam_id::SteamId;
+use crate::steam_requester::AccountInfo;
+
+/// Changed whenever the layout below changes, so older checkpoints are rejected instead of
+/// misread.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Everything a search needs to carry on exactly where it stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub source: SteamId,
+    pub targets: Vec<SteamId>,
+    /// Accounts candidates are scored against, so resuming needs no requests for the targets.
+    pub profiles: Vec<AccountInfo>,
+    pub settings: SearchConfig,
+    pub weights: ScoringWeights,
+    pub results: Vec<TargetResult>,
+    pub graph: FriendGraph,
+    pub failures: Failures,
+    #[serde(default)]
+    pub blacklist: HashSet<SteamId>,
+    pub iterations: usize,
+    pub state: SearchState,
+}
+
+impl Checkpoint {
+    /// Writes to a temporary file first, so a crash while saving leaves the last checkpoint intact.
+    pub fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+
+        let io_error = |err| CheckpointError::Io(path.to_path_buf(), err);
+
+        let mut writer = BufWriter::new(File::create(&temp_path).map_err(io_error)?);
+
+        serde_json::to_writer(&mut writer, self)
+            .map_err(|err| CheckpointError::Json(path.to_path_buf(), err))?;
+
+        writer.flush().map_err(io_error)?;
+        drop(writer);
+
+        fs::rename(&temp_path, path).map_err(io_error)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CheckpointError> {
+        let file = File::open(path).map_err(|err| CheckpointError::Io(path.to_path_buf(), err))?;
+
+        let checkpoint: Checkpoint = serde_json::from_reader(BufReader::new(file))
+            .map_err(|err| CheckpointError::Json(path.to_path_buf(), err))?;
+
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::Version(
+                path.to_path_buf(),
+                checkpoint.version,
+            ));
+        }
+
+        Ok(checkpoint)
+    }
+}
+
+/// Scores are NaN when a target has no games or groups to compare against, and JSON has no NaN,
+/// so they are written as null.
+pub mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_nan() {
+            serializer.serialize_none()
+        } else {
+            serializer.serialize_some(value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        Ok(Option::<f32>::deserialize(deserializer)?.unwrap_or(f32::NAN))
+    }
+}
+
+/// `nan_as_null` for every value of a map of scores.
+pub mod nan_map_as_null {
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K: Serialize, S: Serializer>(
+        scores: &HashMap<K, f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            scores
+                .iter()
+                .map(|(key, score)| (key, (!score.is_nan()).then_some(*score))),
+        )
+    }
+
+    pub fn deserialize<'de, K, D>(deserializer: D) -> Result<HashMap<K, f32>, D::Error>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        D: Deserializer<'de>,
+    {
+        let scores = HashMap::<K, Option<f32>>::deserialize(deserializer)?;
+
+        Ok(scores
+            .into_iter()
+            .map(|(key, score)| (key, score.unwrap_or(f32::NAN)))
+            .collect())
+    }
+}
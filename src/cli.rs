@@ -0,0 +1,336 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, NetworkConfig};
+
+/// Finds chains of friends between Steam accounts.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Settings file to load instead of ./steam_scraper.toml.
+    #[arg(long, global = true, env = "STEAM_SCRAPER_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Search for chains of friends from SOURCE to each TARGET.
+    Search {
+        /// Profile link, vanity name or SteamID of the account to start from.
+        source: String,
+
+        /// Profile links, vanity names or SteamIDs of the accounts to find.
+        #[arg(required = true)]
+        targets: Vec<String>,
+
+        /// Whether candidates are scored against all targets at once or each target separately
+        /// [default: search.target_scoring].
+        #[arg(long, value_enum)]
+        target_scoring: Option<TargetScoring>,
+
+        /// How the friend graph is explored [default: search.strategy].
+        #[arg(long, value_enum)]
+        strategy: Option<Strategy>,
+
+        /// How strongly the astar strategy favours similar accounts over fewer hops. Above 1 the
+        /// path is no longer guaranteed shortest [default: search.heuristic_weight].
+        #[arg(long)]
+        heuristic_weight: Option<f64>,
+
+        /// Number of accounts expanded per iteration [default: search.batch_size].
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        batch_size: Option<u64>,
+
+        /// Maximum number of hops from the source [default: search.max_depth].
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        max_depth: Option<u64>,
+
+        /// Report up to this many distinct paths through the accounts explored
+        /// [default: search.paths].
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        paths: Option<u64>,
+
+        /// Only report paths that share no accounts besides the source and target.
+        #[arg(long)]
+        disjoint: bool,
+
+        /// File to save the search to so it can be resumed [default: checkpoint.path].
+        #[arg(long)]
+        checkpoint: Option<PathBuf>,
+
+        /// Iterations between checkpoints, or 0 to only save when quitting
+        /// [default: checkpoint.every].
+        #[arg(long)]
+        checkpoint_every: Option<u64>,
+    },
+
+    /// Continue a search from a checkpoint, with the settings it was started with.
+    Resume {
+        /// Checkpoint written by an earlier search. It keeps being updated as the search goes on.
+        checkpoint: PathBuf,
+
+        /// Iterations between checkpoints, or 0 to only save when quitting
+        /// [default: checkpoint.every].
+        #[arg(long)]
+        checkpoint_every: Option<u64>,
+    },
+
+    /// Print the details scraped for one account.
+    Profile {
+        /// Profile link, vanity name or SteamID.
+        id: String,
+    },
+
+    /// List the friends of one account.
+    Friends {
+        /// Profile link, vanity name or SteamID.
+        id: String,
+    },
+
+    /// Score how similar account B is to account A.
+    Score {
+        /// Account the score is relative to.
+        a: String,
+
+        /// Account being scored.
+        b: String,
+    },
+
+    /// Walk the friend graph outward from SEED, fetching every account reached.
+    Crawl {
+        /// Profile link, vanity name or SteamID to start from.
+        seed: String,
+
+        /// Number of hops to walk from the seed [default: crawl.depth].
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        depth: Option<u64>,
+
+        /// Stop after this many accounts have been expanded [default: crawl.limit].
+        #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+        limit: Option<u64>,
+    },
+
+    /// Inspect the settings loaded from the config file and environment.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Print the effective settings after every override is applied.
+    Show,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Scrape steamcommunity.com pages.
+    Html,
+    /// Use the official Steam Web API (needs an API key).
+    Api,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Expand the accounts most similar to the target first. Fast, but not always shortest.
+    Greedy,
+    /// Expand accounts by hops so far plus a similarity-based estimate of the hops left.
+    #[value(name = "astar")]
+    AStar,
+    /// Expand accounts in order of hops from the source. Finds a shortest path.
+    Bfs,
+    /// Breadth-first from both ends until the two sides meet. Finds a shortest path.
+    Bidirectional,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetScoring {
+    /// Score against one profile merging the games, groups and country of every target.
+    Combined,
+    /// Score against each target's own profile and keep the best score.
+    PerTarget,
+}
+
+#[derive(Debug, Args)]
+pub struct NetworkArgs {
+    /// Where account data comes from [default: network.backend].
+    #[arg(long, global = true, value_enum)]
+    pub backend: Option<BackendKind>,
+
+    /// Steam Web API key, required by the api backend.
+    #[arg(long, global = true, env = "STEAM_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// Base URL of the Steam Web API, e.g. a local stand-in server.
+    #[arg(long, global = true)]
+    pub api_base_url: Option<String>,
+
+    /// Write every fetched page to this fixture directory.
+    #[arg(long, global = true, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Serve pages from this fixture directory instead of the network.
+    #[arg(long, global = true)]
+    pub replay: Option<PathBuf>,
+
+    /// Do not read or write the on-disk page cache.
+    #[arg(long, global = true, conflicts_with = "refresh")]
+    pub no_cache: bool,
+
+    /// Ignore cached pages but store freshly fetched ones.
+    #[arg(long, global = true)]
+    pub refresh: bool,
+
+    /// Directory of the on-disk page cache [default: network.cache_dir].
+    #[arg(long, global = true)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Seconds a cached page stays fresh [default: network.cache_ttl_secs].
+    #[arg(long, global = true)]
+    pub cache_ttl: Option<u64>,
+
+    /// Requests per second allowed to Steam [default: network.requests_per_second].
+    #[arg(long, global = true, value_parser = parse_positive_f64)]
+    pub rps: Option<f64>,
+
+    /// Requests that may be sent at once after a quiet period [default: network.burst].
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub burst: Option<u32>,
+
+    /// Requests that may be waiting on Steam at the same time [default: network.max_in_flight].
+    #[arg(long, global = true, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_in_flight: Option<u64>,
+
+    /// Attempts made for each request before giving up [default: network.max_attempts].
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_attempts: Option<u32>,
+}
+
+impl NetworkArgs {
+    /// Replaces the configured network settings with any that were given on the command line.
+    fn apply_to(&self, network: &mut NetworkConfig) {
+        if let Some(backend) = self.backend {
+            network.backend = backend;
+        }
+        if let Some(api_key) = &self.api_key {
+            network.api_key = Some(api_key.clone());
+        }
+        if let Some(api_base_url) = &self.api_base_url {
+            network.api_base_url = api_base_url.clone();
+        }
+        if self.no_cache {
+            network.cache = false;
+        }
+        if let Some(cache_dir) = &self.cache_dir {
+            network.cache_dir = cache_dir.clone();
+        }
+        if let Some(cache_ttl) = self.cache_ttl {
+            network.cache_ttl_secs = cache_ttl;
+        }
+        if let Some(rps) = self.rps {
+            network.requests_per_second = rps;
+        }
+        if let Some(burst) = self.burst {
+            network.burst = burst;
+        }
+        if let Some(max_in_flight) = self.max_in_flight {
+            network.max_in_flight = max_in_flight as usize;
+        }
+        if let Some(max_attempts) = self.max_attempts {
+            network.max_attempts = max_attempts;
+        }
+    }
+}
+
+impl Cli {
+    /// Layers the command line flags over `config`.
+    pub fn apply_to(&self, config: &mut Config) {
+        self.network.apply_to(&mut config.network);
+
+        match &self.command {
+            Command::Search {
+                target_scoring,
+                strategy,
+                heuristic_weight,
+                batch_size,
+                max_depth,
+                paths,
+                disjoint,
+                checkpoint,
+                checkpoint_every,
+                ..
+            } => {
+                if let Some(target_scoring) = target_scoring {
+                    config.search.target_scoring = *target_scoring;
+                }
+                if let Some(strategy) = strategy {
+                    config.search.strategy = *strategy;
+                }
+                if let Some(heuristic_weight) = heuristic_weight {
+                    config.search.heuristic_weight = *heuristic_weight;
+                }
+                if let Some(batch_size) = batch_size {
+                    config.search.batch_size = *batch_size as usize;
+                }
+                if let Some(max_depth) = max_depth {
+                    config.search.max_depth = *max_depth as usize;
+                }
+                if let Some(paths) = paths {
+                    config.search.paths = *paths as usize;
+                }
+                if *disjoint {
+                    config.search.node_disjoint = true;
+                }
+                if let Some(checkpoint) = checkpoint {
+                    config.checkpoint.path = checkpoint.clone();
+                }
+                if let Some(checkpoint_every) = checkpoint_every {
+                    config.checkpoint.every = *checkpoint_every as usize;
+                }
+            }
+            Command::Resume {
+                checkpoint,
+                checkpoint_every,
+            } => {
+                config.checkpoint.path = checkpoint.clone();
+
+                if let Some(checkpoint_every) = checkpoint_every {
+                    config.checkpoint.every = *checkpoint_every as usize;
+                }
+            }
+            Command::Crawl { depth, limit, .. } => {
+                if let Some(depth) = depth {
+                    config.crawl.depth = *depth as usize;
+                }
+                if let Some(limit) = limit {
+                    config.crawl.limit = *limit as usize;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn parse_positive_f64(value: &str) -> Result<f64, String> {
+    let parsed = value
+        .parse::<f64>()
+        .map_err(|_| format!("{} is not a number", value))?;
+
+    if parsed > 0_f64 && parsed.is_finite() {
+        Ok(parsed)
+    } else {
+        Err(String::from("must be greater than 0"))
+    }
+}
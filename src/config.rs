@@ -0,0 +1,506 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{BackendKind, Strategy, TargetScoring};
+use crate::error::ConfigError;
+use crate::rate_limit::RateLimit;
+use crate::retry::RetryPolicy;
+use crate::steam_id::SteamId;
+use crate::web_api::DEFAULT_API_BASE_URL;
+
+/// Read from the working directory when no `--config` is given.
+pub const DEFAULT_CONFIG_FILE: &str = "steam_scraper.toml";
+
+/// Environment variables named `STEAM_SCRAPER_<SECTION>_<KEY>` override the config file.
+const ENV_PREFIX: &str = "STEAM_SCRAPER";
+
+/// Settings loaded at startup, layered as defaults < config file < environment < command line.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub network: NetworkConfig,
+    pub scoring: ScoringWeights,
+    pub search: SearchConfig,
+    pub crawl: CrawlConfig,
+    pub checkpoint: CheckpointConfig,
+    pub output: OutputConfig,
+
+    /// File the settings were read from, if any.
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub backend: BackendKind,
+    pub api_key: Option<String>,
+    pub api_base_url: String,
+    pub cache: bool,
+    pub cache_dir: PathBuf,
+    pub cache_ttl_secs: u64,
+    pub requests_per_second: f64,
+    pub burst: u32,
+    pub max_in_flight: usize,
+    pub max_attempts: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        let rate_limit = RateLimit::default();
+        let retry_policy = RetryPolicy::default();
+
+        NetworkConfig {
+            backend: BackendKind::Html,
+            api_key: None,
+            api_base_url: String::from(DEFAULT_API_BASE_URL),
+            cache: true,
+            cache_dir: PathBuf::from(".steam_cache"),
+            cache_ttl_secs: 24 * 60 * 60,
+            requests_per_second: rate_limit.per_second,
+            burst: rate_limit.burst,
+            max_in_flight: rate_limit.max_in_flight,
+            max_attempts: retry_policy.max_attempts,
+            retry_base_delay_ms: retry_policy.base_delay.as_millis() as u64,
+            retry_max_delay_ms: retry_policy.max_delay.as_millis() as u64,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn rate_limit(&self) -> RateLimit {
+        RateLimit {
+            per_second: self.requests_per_second,
+            burst: self.burst,
+            max_in_flight: self.max_in_flight,
+        }
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            base_delay: Duration::from_millis(self.retry_base_delay_ms),
+            max_delay: Duration::from_millis(self.retry_max_delay_ms),
+        }
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_secs)
+    }
+}
+
+/// How much each kind of overlap counts towards `score_account_overlap`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringWeights {
+    pub country: f64,
+    pub recent_games: f64,
+    pub favorite_game: f64,
+    pub friends: f64,
+    pub groups: f64,
+
+    /// Friend count at which the friends score reaches its maximum.
+    pub friend_count_cap: f64,
+}
+
+impl Default for ScoringWeights {
+    fn default() -> Self {
+        ScoringWeights {
+            country: 0.15,
+            recent_games: 0.15,
+            favorite_game: 0.1,
+            friends: 0.1,
+            groups: 0.5,
+            friend_count_cap: 1000_f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub strategy: Strategy,
+    /// Multiplier on the astar heuristic. Paths are only guaranteed shortest up to 1.
+    pub heuristic_weight: f64,
+    /// Number of accounts expanded per iteration. Greedy searches keep this many in flight.
+    pub batch_size: usize,
+    /// Maximum number of hops from the source.
+    pub max_depth: usize,
+    /// Candidates kept in the frontier after each iteration.
+    pub max_frontier: usize,
+    /// Runtime messages that may queue up before the input reader waits.
+    pub message_buffer: usize,
+    /// Number of distinct paths to report once the target is found.
+    pub paths: usize,
+    /// Whether those paths must not share any accounts besides the source and target.
+    pub node_disjoint: bool,
+    /// How candidates are scored when searching for several targets.
+    pub target_scoring: TargetScoring,
+    /// With the api backend, how many accounts of each scored batch, best by their summaries,
+    /// are scored again on their full profiles. Summaries cost one request per 100 accounts and
+    /// full profiles four each, so 0 scores on name and country alone at the lowest cost.
+    pub full_profiles: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            strategy: Strategy::Greedy,
+            heuristic_weight: 1_f64,
+            batch_size: 1000,
+            max_depth: 10,
+            max_frontier: 100000,
+            message_buffer: 100,
+            paths: 1,
+            node_disjoint: false,
+            target_scoring: TargetScoring::PerTarget,
+            full_profiles: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrawlConfig {
+    pub depth: usize,
+    pub limit: usize,
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        CrawlConfig {
+            depth: 2,
+            limit: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CheckpointConfig {
+    /// File searches are saved to, and resumed searches keep saving to.
+    pub path: PathBuf,
+    /// Iterations between saves. A search is also saved when it is quit, even at 0.
+    pub every: usize,
+}
+
+impl Default for CheckpointConfig {
+    fn default() -> Self {
+        CheckpointConfig {
+            path: PathBuf::from("steam_search.checkpoint.json"),
+            every: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Print the accounts that could not be fetched once a command finishes.
+    pub show_failures: bool,
+    pub id_format: IdFormat,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            show_failures: true,
+            id_format: IdFormat::Url,
+        }
+    }
+}
+
+/// How accounts are written in paths and listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdFormat {
+    Url,
+    Id64,
+    Steam2,
+    Steam3,
+}
+
+impl IdFormat {
+    pub fn format(&self, steam_id: &SteamId) -> String {
+        match self {
+            IdFormat::Url => steam_id.profile_url(),
+            IdFormat::Id64 => steam_id.to_string(),
+            IdFormat::Steam2 => steam_id.steam2(),
+            IdFormat::Steam3 => steam_id.steam3(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path`, or `DEFAULT_CONFIG_FILE` if it exists, then applies environment overrides.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let default_path = Path::new(DEFAULT_CONFIG_FILE);
+
+        let source = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None if default_path.is_file() => Some(default_path.to_path_buf()),
+            None => None,
+        };
+
+        let config = match &source {
+            Some(path) => {
+                let raw = std::fs::read_to_string(path)
+                    .map_err(|err| ConfigError::Io(path.clone(), err))?;
+
+                toml::from_str::<Config>(&raw).map_err(ConfigError::Parse)?
+            }
+            None => Config::default(),
+        };
+
+        let mut config = config.with_env_overrides(env::vars())?;
+        config.source = source;
+
+        Ok(config)
+    }
+
+    /// Overrides values with `STEAM_SCRAPER_<SECTION>_<KEY>` variables, parsing each one as the
+    /// type of the value it replaces. Unknown keys are rejected when the result is deserialized.
+    fn with_env_overrides(
+        &self,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let mut table = toml::Table::try_from(self).map_err(ConfigError::Serialize)?;
+
+        for (var, value) in vars {
+            let Some(rest) = var
+                .strip_prefix(ENV_PREFIX)
+                .and_then(|rest| rest.strip_prefix('_'))
+            else {
+                continue;
+            };
+
+            let Some((section, key)) = table
+                .keys()
+                .find(|section| rest.starts_with(&format!("{}_", section.to_uppercase())))
+                .map(|section| (section.clone(), rest[section.len() + 1..].to_lowercase()))
+            else {
+                continue;
+            };
+
+            let Some(toml::Value::Table(entries)) = table.get_mut(&section) else {
+                continue;
+            };
+
+            let parsed = parse_env_value(entries.get(&key), &value)
+                .ok_or_else(|| ConfigError::Env(var.clone(), value.clone()))?;
+
+            entries.insert(key, parsed);
+        }
+
+        toml::Value::Table(table)
+            .try_into::<Config>()
+            .map_err(ConfigError::Parse)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let network = &self.network;
+        let weights = &self.scoring;
+        let search = &self.search;
+
+        let checks = [
+            (
+                network.requests_per_second > 0_f64 && network.requests_per_second.is_finite(),
+                "network.requests_per_second must be greater than 0",
+            ),
+            (network.burst >= 1, "network.burst must be at least 1"),
+            (
+                network.max_in_flight >= 1,
+                "network.max_in_flight must be at least 1",
+            ),
+            (
+                network.max_attempts >= 1,
+                "network.max_attempts must be at least 1",
+            ),
+            (
+                network.retry_base_delay_ms <= network.retry_max_delay_ms,
+                "network.retry_base_delay_ms must not exceed network.retry_max_delay_ms",
+            ),
+            (
+                network.backend != BackendKind::Api || network.api_key.is_some(),
+                "network.api_key is required by the api backend",
+            ),
+            (
+                [
+                    weights.country,
+                    weights.recent_games,
+                    weights.favorite_game,
+                    weights.friends,
+                    weights.groups,
+                ]
+                .iter()
+                .all(|weight| *weight >= 0_f64 && weight.is_finite()),
+                "scoring weights must be finite and not negative",
+            ),
+            (
+                weights.friend_count_cap > 1_f64 && weights.friend_count_cap.is_finite(),
+                "scoring.friend_count_cap must be greater than 1",
+            ),
+            (
+                search.heuristic_weight >= 0_f64 && search.heuristic_weight.is_finite(),
+                "search.heuristic_weight must be finite and not negative",
+            ),
+            (
+                search.batch_size >= 1,
+                "search.batch_size must be at least 1",
+            ),
+            (search.max_depth >= 1, "search.max_depth must be at least 1"),
+            (
+                search.max_frontier >= search.batch_size,
+                "search.max_frontier must be at least search.batch_size",
+            ),
+            (search.paths >= 1, "search.paths must be at least 1"),
+            (
+                search.message_buffer >= 1,
+                "search.message_buffer must be at least 1",
+            ),
+            (self.crawl.depth >= 1, "crawl.depth must be at least 1"),
+            (self.crawl.limit >= 1, "crawl.limit must be at least 1"),
+        ];
+
+        match checks.iter().find(|(ok, _)| !ok) {
+            Some((_, msg)) => Err(ConfigError::Invalid(msg)),
+            None => Ok(()),
+        }
+    }
+
+    /// The effective settings as TOML, with the API key hidden.
+    pub fn to_display_string(&self) -> Result<String, ConfigError> {
+        let mut shown = self.clone();
+
+        if shown.network.api_key.is_some() {
+            shown.network.api_key = Some(String::from("<redacted>"));
+        }
+
+        toml::to_string_pretty(&shown).map_err(ConfigError::Serialize)
+    }
+}
+
+fn parse_env_value(current: Option<&toml::Value>, value: &str) -> Option<toml::Value> {
+    match current {
+        Some(toml::Value::Integer(_)) => value.trim().parse::<i64>().ok().map(toml::Value::from),
+        Some(toml::Value::Float(_)) => value.trim().parse::<f64>().ok().map(toml::Value::from),
+        Some(toml::Value::Boolean(_)) => value.trim().parse::<bool>().ok().map(toml::Value::from),
+        _ => Some(toml::Value::from(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overridden(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        Config::default().with_env_overrides(
+            vars.iter()
+                .map(|(var, value)| (var.to_string(), value.to_string())),
+        )
+    }
+
+    #[test]
+    fn overrides_values_by_their_type() {
+        let config = overridden(&[
+            ("STEAM_SCRAPER_SEARCH_MAX_DEPTH", "4"),
+            ("STEAM_SCRAPER_NETWORK_REQUESTS_PER_SECOND", " 2.5 "),
+            ("STEAM_SCRAPER_SEARCH_NODE_DISJOINT", "true"),
+            ("STEAM_SCRAPER_SEARCH_STRATEGY", "bfs"),
+            ("STEAM_SCRAPER_NETWORK_API_KEY", "KEY"),
+            ("STEAM_SCRAPER_SCORING_GROUPS", "0.25"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.search.max_depth, 4);
+        assert_eq!(config.network.requests_per_second, 2.5_f64);
+        assert!(config.search.node_disjoint);
+        assert_eq!(config.search.strategy, Strategy::Bfs);
+        assert_eq!(config.network.api_key.as_deref(), Some("KEY"));
+        assert_eq!(config.scoring.groups, 0.25_f64);
+    }
+
+    #[test]
+    fn ignores_other_variables() {
+        let config = overridden(&[
+            ("PATH", "/usr/bin"),
+            ("STEAM_SCRAPER_CONFIG", "other.toml"),
+            ("STEAM_SCRAPERX_SEARCH_MAX_DEPTH", "4"),
+            ("STEAM_SCRAPER_NOSUCHSECTION_KEY", "1"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.search.max_depth, SearchConfig::default().max_depth);
+    }
+
+    #[test]
+    fn bad_values_name_the_variable() {
+        for (var, value) in [
+            ("STEAM_SCRAPER_SEARCH_MAX_DEPTH", "four"),
+            ("STEAM_SCRAPER_NETWORK_REQUESTS_PER_SECOND", "fast"),
+            ("STEAM_SCRAPER_NETWORK_CACHE", "yes"),
+        ] {
+            assert!(
+                matches!(
+                    overridden(&[(var, value)]),
+                    Err(ConfigError::Env(name, bad)) if name == var && bad == value
+                ),
+                "{}",
+                var
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(matches!(
+            overridden(&[("STEAM_SCRAPER_SEARCH_MAX_DEPHT", "4")]),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            overridden(&[("STEAM_SCRAPER_SEARCH_STRATEGY", "fastest")]),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(toml::from_str::<Config>("[search]\nmax_dpeth = 4\n").is_err());
+    }
+
+    #[test]
+    fn validate_reports_the_first_bad_setting() {
+        assert!(Config::default().validate().is_ok());
+
+        let mut config = Config::default();
+        config.search.max_frontier = config.search.batch_size - 1;
+
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid(
+                "search.max_frontier must be at least search.batch_size"
+            ))
+        ));
+
+        let mut config = Config::default();
+        config.network.backend = BackendKind::Api;
+
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid(
+                "network.api_key is required by the api backend"
+            ))
+        ));
+
+        config.network.api_key = Some(String::from("KEY"));
+        config.scoring.groups = f64::NAN;
+
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Invalid(
+                "scoring weights must be finite and not negative"
+            ))
+        ));
+    }
+}
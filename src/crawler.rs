@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Arc;
+
+use tokio::task::JoinSet;
+
+use crate::backend::{FriendList, SteamBackend};
+use crate::error::{failure_reason, FetchError};
+use crate::searcher::Failures;
+use crate::steam_id::SteamId;
+
+#[derive(Debug, Clone)]
+pub struct CrawlReport {
+    pub expanded: usize,
+    pub discovered: usize,
+    pub failures: Failures,
+}
+
+type FriendsResult = (SteamId, Result<FriendList, String>);
+
+/// Walks the friend graph breadth first from `seed`, fetching the friends of every account up to
+/// `depth` hops away, without expanding more than `limit` accounts.
+pub async fn crawl(
+    backend: Arc<dyn SteamBackend>,
+    seed: SteamId,
+    depth: usize,
+    limit: usize,
+) -> Result<CrawlReport, Box<dyn Error>> {
+    let mut visited: HashSet<SteamId> = HashSet::from([seed]);
+    let mut frontier = vec![seed];
+    let mut failures: Failures = HashMap::new();
+    let mut expanded = 0_usize;
+
+    for level in 0..depth {
+        if frontier.is_empty() || expanded >= limit {
+            break;
+        }
+
+        frontier.truncate(limit - expanded);
+        expanded += frontier.len();
+
+        println!("Crawling level {}: {} accounts", level + 1, frontier.len());
+
+        let mut tasks: JoinSet<Result<FriendsResult, FetchError>> = JoinSet::new();
+
+        for steam_id in frontier.drain(..) {
+            let backend_ref = Arc::clone(&backend);
+
+            tasks.spawn(async move {
+                match backend_ref.friends(steam_id).await {
+                    Ok(friends) => Ok((steam_id, Ok(friends))),
+                    Err(err) => Ok((steam_id, Err(failure_reason(err)?))),
+                }
+            });
+        }
+
+        for result in tasks.join_all().await {
+            let (steam_id, friends) = result?;
+
+            match friends {
+                Ok(friend_list) => {
+                    println!(
+                        "{}: {} friends",
+                        steam_id.profile_url(),
+                        friend_list.friends.len()
+                    );
+
+                    if let Some(reason) = friend_list.failure() {
+                        failures.insert(steam_id, reason);
+                    }
+
+                    for (_, friend_id) in friend_list.friends {
+                        if visited.insert(friend_id) {
+                            frontier.push(friend_id);
+                        }
+                    }
+                }
+                Err(reason) => {
+                    failures.insert(steam_id, reason);
+                }
+            }
+        }
+    }
+
+    Ok(CrawlReport {
+        expanded: expanded,
+        discovered: visited.len(),
+        failures: failures,
+    })
+}
@@ -1,4 +1,4 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, path::PathBuf, sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub struct SteamError {
@@ -22,3 +22,170 @@ impl Display for SteamError {
 }
 
 impl Error for SteamError {}
+
+#[derive(Debug)]
+pub enum FetchError {
+    Request(reqwest::Error),
+    Status {
+        url: String,
+        code: u16,
+        retry_after: Option<Duration>,
+    },
+    /// Returned by `MemoryFetcher`, which only tests use.
+    #[cfg_attr(not(test), allow(dead_code))]
+    MissingPage(String),
+    MissingFixture(String),
+    Io(std::io::Error),
+    /// A response arrived but could not be read.
+    Malformed(String),
+    /// A request shared by several lookups failed, and each of them gets its error.
+    Batch(Arc<FetchError>),
+}
+
+impl FetchError {
+    /// Errors that mean the whole search can no longer be trusted, rather than one account.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            FetchError::MissingFixture(_) | FetchError::Io(_) => true,
+            FetchError::Batch(err) => err.is_fatal(),
+            _ => false,
+        }
+    }
+
+    /// Errors that may succeed if the same request is made again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FetchError::Request(err) => err.is_timeout() || err.is_connect() || err.is_request(),
+            FetchError::Status { code, .. } => *code == 429 || *code >= 500,
+            FetchError::Batch(err) => err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            FetchError::Status { retry_after, .. } => *retry_after,
+            FetchError::Batch(err) => err.retry_after(),
+            _ => None,
+        }
+    }
+}
+
+/// Splits errors into ones that must stop a search or crawl and failure reasons to record.
+pub fn failure_reason(err: Box<dyn Error>) -> Result<String, FetchError> {
+    match err.downcast::<FetchError>() {
+        Ok(fetch_err) if fetch_err.is_fatal() => Err(*fetch_err),
+        Ok(fetch_err) => Ok(fetch_err.to_string()),
+        Err(err) => Ok(err.to_string()),
+    }
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Request(err) => write!(f, "Request failed: {}", err),
+            FetchError::Status { url, code, .. } => {
+                write!(f, "Request to {} returned status {}", url, code)
+            }
+            FetchError::MissingPage(url) => write!(f, "No page available for {}", url),
+            FetchError::MissingFixture(url) => write!(f, "No recorded fixture for {}", url),
+            FetchError::Io(err) => write!(f, "Fixture IO failed: {}", err),
+            FetchError::Malformed(reason) => write!(f, "Unreadable response: {}", reason),
+            FetchError::Batch(err) => write!(f, "Batched request failed: {}", err),
+        }
+    }
+}
+
+impl Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(err: reqwest::Error) -> Self {
+        FetchError::Request(err)
+    }
+}
+
+impl From<std::io::Error> for FetchError {
+    fn from(err: std::io::Error) -> Self {
+        FetchError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SteamIdError {
+    Invalid(String),
+    Vanity(String),
+}
+
+impl Display for SteamIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SteamIdError::Invalid(input) => write!(f, "Not a recognised SteamID: {}", input),
+            SteamIdError::Vanity(name) => {
+                write!(f, "Vanity name {} must be resolved to a SteamID", name)
+            }
+        }
+    }
+}
+
+impl Error for SteamIdError {}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    Env(String, String),
+    Invalid(&'static str),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => {
+                write!(f, "Could not read config {}: {}", path.display(), err)
+            }
+            ConfigError::Parse(err) => write!(f, "Invalid config: {}", err),
+            ConfigError::Serialize(err) => write!(f, "Could not write config: {}", err),
+            ConfigError::Env(var, value) => {
+                write!(
+                    f,
+                    "Invalid value {:?} for environment variable {}",
+                    value, var
+                )
+            }
+            ConfigError::Invalid(msg) => write!(f, "Invalid config: {}", msg),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(PathBuf, std::io::Error),
+    Json(PathBuf, serde_json::Error),
+    Version(PathBuf, u32),
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(path, err) => {
+                write!(f, "Could not access checkpoint {}: {}", path.display(), err)
+            }
+            CheckpointError::Json(path, err) => {
+                write!(f, "Invalid checkpoint {}: {}", path.display(), err)
+            }
+            CheckpointError::Version(path, version) => {
+                write!(
+                    f,
+                    "Checkpoint {} was written by an incompatible version ({})",
+                    path.display(),
+                    version
+                )
+            }
+        }
+    }
+}
+
+impl Error for CheckpointError {}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use reqwest::header::RETRY_AFTER;
+
+use crate::error::FetchError;
+
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = Result<String, FetchError>> + Send + 'a>>;
+
+/// Source of raw Steam pages, keyed by URL.
+pub trait PageFetcher: Send + Sync {
+    fn fetch(&self, url: String) -> FetchFuture<'_>;
+}
+
+/// How long to wait for a connection to Steam before giving up on it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a whole request may take, so a stalled one fails as a retryable timeout instead of
+/// holding its rate limiter slot forever.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetches pages live from the network.
+#[derive(Debug, Clone)]
+pub struct ReqwestFetcher {
+    client: reqwest::Client,
+}
+
+impl ReqwestFetcher {
+    pub fn new() -> Self {
+        Self::with_timeouts(CONNECT_TIMEOUT, REQUEST_TIMEOUT)
+    }
+
+    pub fn with_timeouts(connect: Duration, request: Duration) -> Self {
+        ReqwestFetcher {
+            client: reqwest::Client::builder()
+                .connect_timeout(connect)
+                .timeout(request)
+                .build()
+                .expect("HTTP client settings are always valid"),
+        }
+    }
+}
+
+impl Default for ReqwestFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PageFetcher for ReqwestFetcher {
+    fn fetch(&self, url: String) -> FetchFuture<'_> {
+        Box::pin(async move {
+            let response = self.client.get(url.clone()).send().await?;
+            let status = response.status();
+
+            if !status.is_success() {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+
+                return Err(FetchError::Status {
+                    url: url,
+                    code: status.as_u16(),
+                    retry_after: retry_after,
+                });
+            }
+
+            let page = response.text().await?;
+
+            Ok(page)
+        })
+    }
+}
+
+/// Serves pages from memory, for running against recorded pages with no network. Only tests
+/// construct it in this binary.
+#[cfg_attr(not(test), allow(dead_code))]
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFetcher {
+    pages: HashMap<String, String>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl MemoryFetcher {
+    pub fn new() -> Self {
+        MemoryFetcher {
+            pages: HashMap::new(),
+        }
+    }
+
+    pub fn from_pages(pages: HashMap<String, String>) -> Self {
+        MemoryFetcher { pages: pages }
+    }
+
+    pub fn insert(&mut self, url: String, page: String) {
+        self.pages.insert(url, page);
+    }
+}
+
+impl PageFetcher for MemoryFetcher {
+    fn fetch(&self, url: String) -> FetchFuture<'_> {
+        let page = self.pages.get(&url).cloned();
+
+        Box::pin(async move { page.ok_or(FetchError::MissingPage(url)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stalled_requests_time_out() {
+        // Accepts connections but never answers.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let fetcher =
+            ReqwestFetcher::with_timeouts(Duration::from_secs(1), Duration::from_millis(200));
+        let err = fetcher.fetch(url).await.unwrap_err();
+
+        assert!(matches!(&err, FetchError::Request(err) if err.is_timeout()));
+        assert!(err.is_retryable());
+    }
+}
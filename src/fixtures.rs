@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::error::FetchError;
+use crate::fetcher::{FetchFuture, PageFetcher};
+
+/// Drops the Web API key from a URL so it never ends up in fixture or cache file names.
+fn without_api_key(url: &str) -> String {
+    let mut parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return url.to_string(),
+    };
+
+    if !parsed.query_pairs().any(|(name, _)| name == "key") {
+        return url.to_string();
+    }
+
+    let kept = parsed
+        .query_pairs()
+        .filter(|(name, _)| name != "key")
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect::<Vec<(String, String)>>();
+
+    parsed.query_pairs_mut().clear().extend_pairs(kept);
+
+    parsed.to_string()
+}
+
+/// Longest file name kept readable. Batched API URLs list up to 100 SteamIDs, far past the 255
+/// byte limit most filesystems have, so longer names are cut short and made unique with a hash.
+const MAX_READABLE_NAME: usize = 200;
+
+/// Maps a URL to a flat file name so each fixture is keyed by the URL it came from. Short URLs map
+/// reversibly, long ones to a readable prefix and a hash of the whole URL.
+pub fn fixture_name(url: &str) -> String {
+    let url = without_api_key(url);
+    let mut name = String::new();
+
+    for byte in url.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'.' {
+            name.push(byte as char);
+        } else {
+            name.push_str(format!("_{:02X}", byte).as_str());
+        }
+    }
+
+    if name.len() > MAX_READABLE_NAME {
+        // Only ASCII is pushed above, so any length is a char boundary.
+        name.truncate(MAX_READABLE_NAME - 17);
+        name.push_str(format!("~{:016x}", fnv1a(url.as_bytes())).as_str());
+    }
+
+    name + ".html"
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` it is fixed, so names stay the same between builds.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn fixture_path(dir: &Path, url: &str) -> PathBuf {
+    dir.join(fixture_name(url))
+}
+
+/// Numbers temporary files, so concurrent writes of the same page never share one.
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// File a page is written to before being renamed to `path`, unique to each write.
+fn temp_path(path: &Path) -> PathBuf {
+    path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Writes `page` aside then renames it to `path`, so readers never see a partial page and
+/// concurrent writes of one URL cannot interleave.
+pub async fn write_page(path: &Path, page: &str) -> std::io::Result<()> {
+    let temp_path = temp_path(path);
+
+    if let Err(err) = tokio::fs::write(&temp_path, page).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(err);
+    }
+
+    tokio::fs::rename(&temp_path, path).await
+}
+
+/// Passes fetches through to another fetcher, writing every page it returns to the fixture directory.
+pub struct RecordingFetcher {
+    inner: Arc<dyn PageFetcher>,
+    dir: PathBuf,
+}
+
+impl RecordingFetcher {
+    pub fn new(inner: Arc<dyn PageFetcher>, dir: PathBuf) -> Result<Self, FetchError> {
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(RecordingFetcher {
+            inner: inner,
+            dir: dir,
+        })
+    }
+}
+
+impl PageFetcher for RecordingFetcher {
+    fn fetch(&self, url: String) -> FetchFuture<'_> {
+        Box::pin(async move {
+            let page = self.inner.fetch(url.clone()).await?;
+
+            write_page(&fixture_path(&self.dir, &url), &page).await?;
+
+            Ok(page)
+        })
+    }
+}
+
+/// Serves pages previously written by a `RecordingFetcher`, never touching the network.
+pub struct ReplayFetcher {
+    dir: PathBuf,
+}
+
+impl ReplayFetcher {
+    pub fn new(dir: PathBuf) -> Self {
+        ReplayFetcher { dir: dir }
+    }
+}
+
+impl PageFetcher for ReplayFetcher {
+    fn fetch(&self, url: String) -> FetchFuture<'_> {
+        Box::pin(async move {
+            match tokio::fs::read_to_string(fixture_path(&self.dir, &url)).await {
+                Ok(page) => Ok(page),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    Err(FetchError::MissingFixture(url))
+                }
+                Err(err) => Err(FetchError::Io(err)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::MemoryFetcher;
+
+    fn summaries_url(count: u64) -> String {
+        let ids = (0..count)
+            .map(|index| (76561197960265728 + index).to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        format!(
+            "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v0002/?key=SECRET&steamids={}",
+            ids
+        )
+    }
+
+    #[test]
+    fn short_urls_keep_readable_names() {
+        assert_eq!(
+            fixture_name("https://steamcommunity.com/profiles/76561197960265729"),
+            "https_3A_2F_2Fsteamcommunity.com_2Fprofiles_2F76561197960265729.html"
+        );
+    }
+
+    #[test]
+    fn long_urls_are_hashed_within_the_name_limit() {
+        let full = fixture_name(&summaries_url(100));
+        let shorter = fixture_name(&summaries_url(99));
+
+        assert!(full.len() <= 255);
+        assert!(full.starts_with("https_3A_2F_2Fapi.steampowered.com"));
+        assert!(!full.contains("SECRET"));
+        assert_ne!(full, shorter);
+        assert_eq!(full, fixture_name(&summaries_url(100)));
+    }
+
+    #[test]
+    fn every_write_gets_its_own_temp_file() {
+        let path = Path::new("cache/page.html");
+
+        assert_ne!(temp_path(path), temp_path(path));
+        assert!(temp_path(path).to_string_lossy().ends_with(".tmp"));
+    }
+
+    #[tokio::test]
+    async fn concurrent_recordings_leave_one_whole_fixture() {
+        let dir = std::env::temp_dir().join(format!("steam_recording_{}", std::process::id()));
+        let url = String::from("https://steamcommunity.com/profiles/76561197960265729");
+        let page = "x".repeat(1 << 20);
+
+        let mut pages = MemoryFetcher::new();
+        pages.insert(url.clone(), page.clone());
+
+        let recorder = Arc::new(RecordingFetcher::new(Arc::new(pages), dir.clone()).unwrap());
+        let mut fetches = tokio::task::JoinSet::new();
+
+        for _ in 0..16 {
+            let recorder = Arc::clone(&recorder);
+            let url = url.clone();
+
+            fetches.spawn(async move { recorder.fetch(url).await.unwrap() });
+        }
+
+        fetches.join_all().await;
+
+        let replayed = ReplayFetcher::new(dir.clone()).fetch(url).await;
+        let leftovers = std::fs::read_dir(&dir).unwrap().count();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(replayed.unwrap(), page);
+        assert_eq!(leftovers, 1);
+    }
+
+    #[tokio::test]
+    async fn long_urls_record_and_replay() {
+        let dir = std::env::temp_dir().join(format!("steam_fixtures_{}", std::process::id()));
+        let url = summaries_url(100);
+
+        let mut pages = MemoryFetcher::new();
+        pages.insert(url.clone(), String::from("{}"));
+
+        let recorder = RecordingFetcher::new(Arc::new(pages), dir.clone()).unwrap();
+        recorder.fetch(url.clone()).await.unwrap();
+
+        let replayed = ReplayFetcher::new(dir.clone()).fetch(url).await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(replayed.unwrap(), "{}");
+    }
+}
@@ -1,44 +1,92 @@
-use std::{cmp::min, collections::HashSet, hash::Hash, vec::IntoIter};
+use std::{cmp::Ordering, collections::HashMap, hash::Hash, iter::FusedIterator, vec::IntoIter};
 
-pub enum Order {
-    Greater,
-    Equal,
-    Smaller,
+/// Decides which of two items comes out of a heap first: the greater one. Implemented for any
+/// `Fn(&T, &T) -> Ordering`, so closures can capture what they compare with.
+pub trait Comparator<T> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
 }
 
+impl<T, F: Fn(&T, &T) -> Ordering> Comparator<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// An `f32` with a total order, for scores that can be NaN. NaN is equal to itself and below
+/// every number, so items without a score come out of a heap last.
+#[derive(Clone, Copy, Debug)]
+pub struct OrderedF32(pub f32);
+
+impl Ord for OrderedF32 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.0.is_nan(), other.0.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => self.0.total_cmp(&other.0),
+        }
+    }
+}
+
+impl PartialOrd for OrderedF32 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OrderedF32 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrderedF32 {}
+
+/// Max-heap holding at most one item per key, as given by `key`.
 #[derive(Clone, Debug)]
-pub struct MaxHeap<T: Clone, U: Hash + Eq + Clone> {
+pub struct MaxHeap<
+    T: Clone,
+    U: Hash + Eq + Clone,
+    C: Comparator<T> = fn(&T, &T) -> Ordering,
+    K: Fn(&T) -> &U = fn(&T) -> &U,
+> {
     data: Vec<T>,
-    keys: HashSet<U>,
+    /// Index into `data` of the item with each key.
+    positions: HashMap<U, usize>,
     len: usize,
-    cmp: fn(&T, &T) -> Order,
-    key: fn(&T) -> &U,
+    cmp: C,
+    key: K,
 }
 
-impl<T: Clone, U: Hash + Eq + Clone> MaxHeap<T, U> {
-    pub fn new(cmp: fn(&T, &T) -> Order, key: fn(&T) -> &U) -> Self {
-        Self {
-            data: Vec::new(),
-            keys: HashSet::new(),
-            len: 0,
-            cmp: cmp,
-            key: key,
-        }
+impl<T: Clone + Ord + Hash + Eq> MaxHeap<T, T> {
+    /// Heap of items ordered by `Ord`, each being its own key.
+    pub fn by_ord() -> Self {
+        Self::new(T::cmp, identity)
     }
+}
 
-    pub fn new_similar(&self) -> Self {
+fn identity<T>(item: &T) -> &T {
+    item
+}
+
+impl<T: Clone, U: Hash + Eq + Clone, C: Comparator<T>, K: Fn(&T) -> &U> MaxHeap<T, U, C, K> {
+    pub fn new(cmp: C, key: K) -> Self {
         Self {
             data: Vec::new(),
-            keys: HashSet::new(),
+            positions: HashMap::new(),
             len: 0,
-            cmp: self.cmp.clone(),
-            key: self.key.clone(),
+            cmp: cmp,
+            key: key,
         }
     }
 
     pub fn sink(&mut self, index: usize) {
         if let Some(smallest_child) = self.largest_child(index) {
-            if let Order::Smaller = (self.cmp)(&self.data[index], &self.data[smallest_child]) {
+            if self
+                .cmp
+                .compare(&self.data[index], &self.data[smallest_child])
+                == Ordering::Less
+            {
                 self.swap(index, smallest_child);
                 self.sink(smallest_child);
             }
@@ -47,7 +95,7 @@ impl<T: Clone, U: Hash + Eq + Clone> MaxHeap<T, U> {
 
     pub fn swim(&mut self, index: usize) {
         if let Some(parent) = self.parent(index) {
-            if let Order::Smaller = (self.cmp)(&self.data[parent], &self.data[index]) {
+            if self.cmp.compare(&self.data[parent], &self.data[index]) == Ordering::Less {
                 self.swap(parent, index);
                 self.swim(parent);
             }
@@ -55,20 +103,108 @@ impl<T: Clone, U: Hash + Eq + Clone> MaxHeap<T, U> {
     }
 
     pub fn in_heap(&self, item: &T) -> bool {
-        self.keys.contains((self.key)(item))
+        self.contains((self.key)(item))
+    }
+
+    pub fn contains(&self, key: &U) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    pub fn get(&self, key: &U) -> Option<&T> {
+        self.positions.get(key).map(|index| &self.data[*index])
     }
 
+    /// Adds `item` unless an item with the same key is already queued.
     pub fn insert(&mut self, item: T) {
+        if self.push_unsifted(item) {
+            self.swim(self.len - 1);
+        }
+    }
+
+    /// Adds every item whose key is not queued yet, like `insert`. A large batch is pushed as is
+    /// and the whole heap rebuilt in O(n), rather than sifting each item up on its own.
+    pub fn merge<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        let start = self.len;
+
+        for item in items {
+            self.push_unsifted(item);
+        }
+
+        let added = self.len - start;
+        let depth = (usize::BITS - self.len.leading_zeros()) as usize;
+
+        // Rebuilding costs about two comparisons per item, sifting up one per level per new item.
+        if 2 * self.len < added * depth {
+            self.heapify();
+        } else {
+            for index in start..self.len {
+                self.swim(index);
+            }
+        }
+    }
+
+    /// Moves every item of `other` into this heap, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        other.positions.clear();
+        other.len = 0;
+
+        self.merge(std::mem::take(&mut other.data));
+    }
+
+    /// Adds `item` to the end of `data` without restoring the heap property. Returns false,
+    /// dropping it, if an item with the same key is already queued.
+    fn push_unsifted(&mut self, item: T) -> bool {
         if self.in_heap(&item) {
-            return;
+            return false;
         }
 
-        self.keys.insert((self.key)(&item).clone());
+        self.positions.insert((self.key)(&item).clone(), self.len);
 
         self.data.push(item);
         self.len += 1;
 
-        self.swim(self.len - 1);
+        true
+    }
+
+    /// Restores the heap property over all of `data`, sinking every parent from the bottom up.
+    fn heapify(&mut self) {
+        for index in (0..self.len / 2).rev() {
+            self.sink(index);
+        }
+    }
+
+    /// Replaces the queued item with the same key as `item`, moving it up or down to match its
+    /// new priority. Returns false, changing nothing, if no such item is queued.
+    pub fn update_priority(&mut self, item: T) -> bool {
+        let Some(index) = self.positions.get((self.key)(&item)).copied() else {
+            return false;
+        };
+
+        self.data[index] = item;
+
+        self.sink(index);
+        self.swim(index);
+
+        true
+    }
+
+    pub fn remove(&mut self, key: &U) -> Option<T> {
+        let index = self.positions.get(key).copied()?;
+
+        self.swap(index, self.len - 1);
+
+        let result = self.data.pop();
+        self.len -= 1;
+
+        self.positions.remove(key);
+
+        // The last item now fills the gap and may belong above or below it.
+        if index < self.len {
+            self.sink(index);
+            self.swim(index);
+        }
+
+        result
     }
 
     pub fn peek(&self) -> Option<&T> {
@@ -76,39 +212,32 @@ impl<T: Clone, U: Hash + Eq + Clone> MaxHeap<T, U> {
     }
 
     pub fn pop(&mut self) -> Option<T> {
-        if self.data.is_empty() {
+        if self.is_empty() {
             return None;
         }
 
-        self.data.swap(0, self.len - 1);
+        self.swap(0, self.len - 1);
 
         let result = self.data.pop();
         self.len -= 1;
 
+        if let Some(item) = &result {
+            self.positions.remove((self.key)(item));
+        }
+
         self.sink(0);
 
         return result;
     }
 
-    pub fn pop_many(&mut self, amount: usize) -> Vec<T> {
-        let real_amount = min(self.len, amount);
-        let mut result = Vec::new();
-        let mut cur_index = 0_usize;
-
-        while let Some(next) = self.pop() {
-            result.push(next);
-            cur_index += 1;
+    fn swap(&mut self, a: usize, b: usize) {
+        self.data.swap(a, b);
 
-            if cur_index >= real_amount {
-                break;
+        for index in [a, b] {
+            if let Some(position) = self.positions.get_mut((self.key)(&self.data[index])) {
+                *position = index;
             }
         }
-
-        return result;
-    }
-
-    fn swap(&mut self, a: usize, b: usize) {
-        self.data.swap(a, b);
     }
 
     fn parent(&self, index: usize) -> Option<usize> {
@@ -128,7 +257,7 @@ impl<T: Clone, U: Hash + Eq + Clone> MaxHeap<T, U> {
             None
         } else if right >= self.data.len() {
             Some(left)
-        } else if let Order::Greater = (self.cmp)(&self.data[left], &self.data[right]) {
+        } else if self.cmp.compare(&self.data[left], &self.data[right]) == Ordering::Greater {
             Some(left)
         } else {
             Some(right)
@@ -144,22 +273,304 @@ impl<T: Clone, U: Hash + Eq + Clone> MaxHeap<T, U> {
         self.data.clone().into_iter()
     }
 
-    pub fn combine_with(&self, other: &MaxHeap<T, U>) -> Self {
-        let mut new_heap = self.clone();
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        for item in other.unsorted_iter() {
-            new_heap.insert(item);
-        }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
 
-        new_heap
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.positions.clear();
+        self.len = 0;
     }
 
-    pub fn len(&self) -> usize {
-        self.len
+    /// Removes every item, highest priority first. Items left when the iterator is dropped are
+    /// removed too.
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, T, U, C, K> {
+        DrainSorted { heap: self }
     }
 
+    /// Keeps only the `size` items with the highest priority.
     pub fn truncate(&mut self, size: usize) {
-        self.data.truncate(size);
-        self.len = self.data.len();
+        if size >= self.len {
+            return;
+        }
+
+        // Drained in priority order, so every item comes after its parent and the kept items
+        // already form a heap. The rest are dropped with the iterator.
+        let kept: Vec<T> = self.drain_sorted().take(size).collect();
+
+        self.positions = kept
+            .iter()
+            .enumerate()
+            .map(|(index, item)| ((self.key)(item).clone(), index))
+            .collect();
+        self.data = kept;
+        self.len = size;
+    }
+}
+
+impl<T: Clone, U: Hash + Eq + Clone, C: Comparator<T>, K: Fn(&T) -> &U> Extend<T>
+    for MaxHeap<T, U, C, K>
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        self.merge(items);
+    }
+}
+
+impl<T: Clone + Ord + Hash + Eq> FromIterator<T> for MaxHeap<T, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let mut heap = Self::by_ord();
+        heap.extend(items);
+        heap
+    }
+}
+
+pub struct DrainSorted<'a, T: Clone, U: Hash + Eq + Clone, C: Comparator<T>, K: Fn(&T) -> &U> {
+    heap: &'a mut MaxHeap<T, U, C, K>,
+}
+
+impl<T: Clone, U: Hash + Eq + Clone, C: Comparator<T>, K: Fn(&T) -> &U> Iterator
+    for DrainSorted<'_, T, U, C, K>
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.heap.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len, Some(self.heap.len))
+    }
+}
+
+impl<T: Clone, U: Hash + Eq + Clone, C: Comparator<T>, K: Fn(&T) -> &U> ExactSizeIterator
+    for DrainSorted<'_, T, U, C, K>
+{
+}
+
+impl<T: Clone, U: Hash + Eq + Clone, C: Comparator<T>, K: Fn(&T) -> &U> FusedIterator
+    for DrainSorted<'_, T, U, C, K>
+{
+}
+
+impl<T: Clone, U: Hash + Eq + Clone, C: Comparator<T>, K: Fn(&T) -> &U> Drop
+    for DrainSorted<'_, T, U, C, K>
+{
+    fn drop(&mut self) {
+        self.heap.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Priority and key.
+    type Item = (i32, u32);
+
+    fn priority(a: &Item, b: &Item) -> Ordering {
+        a.0.cmp(&b.0)
+    }
+
+    fn key(item: &Item) -> &u32 {
+        &item.1
+    }
+
+    /// Xorshift, so a failing sequence of operations can be replayed from its seed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
+
+        fn item(&mut self) -> Item {
+            (self.below(50) as i32 - 25, self.below(40) as u32)
+        }
+    }
+
+    /// Checks the heap property and `positions` against `data`, and the queued items against
+    /// `expected`, a map from key to priority.
+    fn check(heap: &MaxHeap<Item, u32>, expected: &HashMap<u32, i32>) {
+        assert_eq!(heap.len, heap.data.len());
+        assert_eq!(heap.positions.len(), heap.data.len());
+
+        for (index, item) in heap.data.iter().enumerate() {
+            assert_eq!(heap.positions[&item.1], index);
+
+            if let Some(parent) = heap.parent(index) {
+                assert!(heap.data[parent].0 >= item.0, "{:?}", heap.data);
+            }
+        }
+
+        let queued = heap
+            .data
+            .iter()
+            .map(|(priority, key)| (*key, *priority))
+            .collect::<HashMap<u32, i32>>();
+
+        assert_eq!(&queued, expected);
+    }
+
+    fn highest(expected: &HashMap<u32, i32>) -> Option<i32> {
+        expected.values().max().copied()
+    }
+
+    #[test]
+    fn nan_is_equal_to_itself_and_below_every_number() {
+        let nan = OrderedF32(f32::NAN);
+
+        assert_eq!(nan, OrderedF32(f32::NAN));
+        assert!(nan < OrderedF32(f32::NEG_INFINITY));
+        assert!(OrderedF32(-1_f32) < OrderedF32(1_f32));
+
+        let mut scores = [
+            OrderedF32(1_f32),
+            nan,
+            OrderedF32(-3_f32),
+            OrderedF32(2_f32),
+        ];
+        scores.sort();
+
+        let scores = scores.iter().map(|score| score.0).collect::<Vec<f32>>();
+        assert!(scores[0].is_nan());
+        assert_eq!(scores[1..], [-3_f32, 1_f32, 2_f32]);
+    }
+
+    #[test]
+    fn drain_sorted_yields_highest_first_and_empties_the_heap() {
+        let mut heap: MaxHeap<u32, u32> = [4, 9, 1, 7, 3, 9].into_iter().collect();
+
+        // Duplicates share a key, so only one 9 is queued.
+        assert_eq!(heap.len(), 5);
+
+        let mut drain = heap.drain_sorted();
+        assert_eq!(drain.len(), 5);
+        assert_eq!(drain.next(), Some(9));
+        assert_eq!(drain.next(), Some(7));
+        drop(drain);
+
+        assert!(heap.is_empty());
+        assert!(!heap.contains(&4));
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn extend_skips_queued_keys() {
+        let mut heap: MaxHeap<Item, u32> = MaxHeap::new(priority, key);
+        heap.insert((5, 1));
+
+        heap.extend([(8, 1), (2, 2), (6, 3)]);
+
+        assert_eq!(
+            heap.drain_sorted().collect::<Vec<Item>>(),
+            [(6, 3), (5, 1), (2, 2)]
+        );
+    }
+
+    #[test]
+    fn key_can_be_a_closure() {
+        let mut heap = MaxHeap::new(priority, |item: &Item| &item.1);
+
+        heap.insert((3, 1));
+        heap.insert((4, 2));
+        heap.insert((9, 2));
+
+        assert!(heap.contains(&1));
+        assert_eq!(heap.remove(&2), Some((4, 2)));
+        assert_eq!(heap.pop(), Some((3, 1)));
+    }
+
+    #[test]
+    fn random_operations_keep_the_heap_consistent() {
+        for seed in 1..=50_u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let mut heap: MaxHeap<Item, u32> = MaxHeap::new(priority, key);
+            let mut expected: HashMap<u32, i32> = HashMap::new();
+
+            for _ in 0..300 {
+                match rng.below(8) {
+                    0 | 1 => {
+                        let item = rng.item();
+                        expected.entry(item.1).or_insert(item.0);
+                        heap.insert(item);
+                    }
+                    2 => {
+                        let popped = heap.pop();
+                        assert_eq!(popped.map(|item| item.0), highest(&expected));
+
+                        if let Some((_, key)) = popped {
+                            expected.remove(&key);
+                        }
+                    }
+                    3 => {
+                        let key = rng.below(40) as u32;
+                        let removed = heap.remove(&key);
+                        assert_eq!(removed.map(|item| item.0), expected.remove(&key));
+                    }
+                    4 => {
+                        let item = rng.item();
+                        let queued = expected.contains_key(&item.1);
+                        assert_eq!(heap.update_priority(item), queued);
+
+                        if queued {
+                            expected.insert(item.1, item.0);
+                        }
+                    }
+                    5 => {
+                        let size = rng.below(30) as usize;
+
+                        let mut priorities = expected.values().copied().collect::<Vec<i32>>();
+                        priorities.sort_by(|a, b| b.cmp(a));
+                        priorities.truncate(size);
+
+                        heap.truncate(size);
+
+                        let mut kept = heap.data.iter().map(|item| item.0).collect::<Vec<i32>>();
+                        kept.sort_by(|a, b| b.cmp(a));
+                        assert_eq!(kept, priorities);
+
+                        // Ties at the cut may keep either item, so follow the heap's choice.
+                        expected.retain(|key, _| heap.contains(key));
+                    }
+                    6 => {
+                        let items = (0..rng.below(20))
+                            .map(|_| rng.item())
+                            .collect::<Vec<Item>>();
+
+                        for item in items.iter() {
+                            expected.entry(item.1).or_insert(item.0);
+                        }
+
+                        heap.merge(items);
+                    }
+                    _ => {
+                        let mut other: MaxHeap<Item, u32> = MaxHeap::new(priority, key);
+
+                        for _ in 0..rng.below(10) {
+                            let item = rng.item();
+                            other.insert(item);
+                        }
+
+                        for (priority, key) in other.data.iter() {
+                            expected.entry(*key).or_insert(*priority);
+                        }
+
+                        heap.append(&mut other);
+                        assert_eq!(other.len(), 0);
+                        assert!(other.positions.is_empty());
+                    }
+                }
+
+                check(&heap, &expected);
+            }
+        }
     }
 }
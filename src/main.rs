@@ -1,128 +1,572 @@
-use msg::Message;
-use std::{env, error::Error, os::windows::thread};
+use clap::Parser;
+use msg::{Message, Request, Response};
+use std::{error::Error, sync::Arc};
 use tokio::{
-    sync::mpsc::{self, Receiver, Sender},
+    sync::mpsc::{self, Receiver, Sender, UnboundedReceiver},
     task::JoinSet,
 };
+use tokio_util::sync::CancellationToken;
 
-use searcher::Searcher;
-use steam_requester::test_account_build_info;
+use backend::{HtmlBackend, SteamBackend};
+use checkpoint::Checkpoint;
+use cli::{BackendKind, Cli, Command, ConfigAction, NetworkArgs, Strategy};
+use config::{Config, NetworkConfig, OutputConfig};
+use error::SteamError;
+use fetcher::{PageFetcher, ReqwestFetcher};
+use fixtures::{RecordingFetcher, ReplayFetcher};
+use page_cache::{CacheMode, CachedFetcher};
+use rate_limit::{RateLimiter, ThrottledFetcher};
+use retry::RetryingFetcher;
+use searcher::{SearchOutcome, Searcher, TargetResult};
+use steam_id::SteamId;
+use steam_requester::{score_account_overlap, test_account_build_info};
+use web_api::WebApiBackend;
 
 extern crate reqwest;
 extern crate tl;
 extern crate tokio;
 
+mod backend;
+mod checkpoint;
+mod cli;
+mod config;
+mod crawler;
 mod error;
+mod fetcher;
+mod fixtures;
 mod heap;
 mod log;
 mod msg;
+mod page_cache;
+mod paths;
 mod process_runner;
+mod rate_limit;
+mod resolver;
+mod retry;
 mod searcher;
+mod steam_id;
 mod steam_requester;
 mod util;
+mod web_api;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let searcher = Searcher::new(
-        "https://steamcommunity.com/profiles/76561198258961896",
-        "https://steamcommunity.com/id/Undeadnemesiss",
-    )
-    .await;
+    let cli = Cli::parse();
 
-    let mut args = env::args();
+    let mut config = Config::load(cli.config.as_deref())?;
+    cli.apply_to(&mut config);
+    config.validate()?;
 
-    args.next();
+    if let Command::Config {
+        action: ConfigAction::Show,
+    } = cli.command
+    {
+        match &config.source {
+            Some(path) => println!("# Loaded from {}", path.display()),
+            None => println!("# No config file found, using defaults"),
+        }
+
+        print!("{}", config.to_display_string()?);
+        return Ok(());
+    }
+
+    let FetchStack {
+        backend,
+        cache,
+        limiter,
+    } = build_backend(&config.network, &cli.network)?;
+    let output = config.output;
+
+    match cli.command {
+        Command::Search {
+            source, targets, ..
+        } => {
+            let searcher = Searcher::new(
+                backend,
+                config.search,
+                config.scoring,
+                config.checkpoint.clone(),
+                &source,
+                &targets,
+            )
+            .await?;
+
+            run_search(searcher, output, limiter).await;
+        }
+        Command::Resume { checkpoint, .. } => {
+            let saved = Checkpoint::load(&checkpoint)?;
+
+            println!(
+                "Resuming from {} after {} iterations",
+                checkpoint.display(),
+                saved.iterations
+            );
+
+            let searcher = Searcher::resume(backend, saved, config.checkpoint.clone());
+
+            run_search(searcher, output, limiter).await;
+        }
+        Command::Profile { id } => {
+            let steam_id = backend.resolve(id).await?;
+            let account = backend.account_info(steam_id).await?;
+
+            println!("SteamID64: {}", steam_id);
+            println!("Steam2: {}", steam_id.steam2());
+            println!("Steam3: {}", steam_id.steam3());
+            println!("Profile: {}", steam_id.profile_url());
+            println!("{:#?}", account);
+        }
+        Command::Friends { id } => {
+            let steam_id = backend.resolve(id).await?;
+
+            let friend_list = backend.friends(steam_id).await?;
+
+            for (name, friend_id) in friend_list.friends {
+                println!("{} {}", output.id_format.format(&friend_id), name);
+            }
+
+            for (link, reason) in friend_list.unresolved {
+                println!("Unresolved: {} ({})", link, reason);
+            }
+        }
+        Command::Score { a, b } => {
+            let base_account = backend.account_info(backend.resolve(a).await?).await?;
+            let scored_account = backend.account_info(backend.resolve(b).await?).await?;
+
+            println!(
+                "{}",
+                score_account_overlap(&base_account, &scored_account, &config.scoring)
+            );
+        }
+        Command::Crawl { seed, .. } => {
+            let seed_id = backend.resolve(seed).await?;
+            let report =
+                crawler::crawl(backend, seed_id, config.crawl.depth, config.crawl.limit).await?;
+
+            print_failures(&report.failures, &output);
+
+            println!(
+                "Expanded: {}, Discovered: {}, Failed: {}",
+                report.expanded,
+                report.discovered,
+                report.failures.len()
+            );
+        }
+        Command::Config { .. } => {}
+    }
+
+    if let Some(cache) = cache {
+        let stats = cache.stats();
+        println!("Cache hits: {}, Cache misses: {}", stats.hits, stats.misses);
+    }
+
+    Ok(())
+}
 
-    let chunk_size = match args.next() {
-        Some(size) => size
-            .parse::<usize>()
-            .expect("Expects first argument to be usize!"),
-        None => 1000_usize,
+/// The chosen backend and the parts of its fetcher stack a command still needs.
+struct FetchStack {
+    backend: Arc<dyn SteamBackend>,
+    /// Page cache, so its counters can be reported once the command finishes.
+    cache: Option<Arc<CachedFetcher>>,
+    /// Rate limiter, so it can be adjusted while a search runs.
+    limiter: Arc<RateLimiter>,
+}
+
+/// Stacks the fetchers the network settings ask for under the chosen backend.
+fn build_backend(
+    network: &NetworkConfig,
+    args: &NetworkArgs,
+) -> Result<FetchStack, Box<dyn Error>> {
+    let limiter = Arc::new(RateLimiter::new(network.rate_limit()));
+
+    let live_fetcher: Arc<dyn PageFetcher> = Arc::new(RetryingFetcher::new(
+        Arc::new(ThrottledFetcher::new(
+            Arc::new(ReqwestFetcher::new()),
+            limiter.clone(),
+        )),
+        network.retry_policy(),
+    ));
+
+    let cache = if !network.cache || args.replay.is_some() {
+        None
+    } else {
+        Some(Arc::new(CachedFetcher::new(
+            live_fetcher.clone(),
+            network.cache_dir.clone(),
+            network.cache_ttl(),
+            if args.refresh {
+                CacheMode::Refresh
+            } else {
+                CacheMode::Normal
+            },
+        )?))
     };
 
-    let max_depth = match args.next() {
-        Some(depth) => depth
-            .parse::<usize>()
-            .expect("Expects second argument to be usize!"),
-        None => 10_usize,
+    let cached_fetcher: Arc<dyn PageFetcher> = match &cache {
+        Some(cache) => cache.clone(),
+        None => live_fetcher,
     };
 
-    println!("Chunk size: {}, Max depth: {}", chunk_size, max_depth);
+    let fetcher: Arc<dyn PageFetcher> = match (&args.record, &args.replay) {
+        (Some(dir), _) => Arc::new(RecordingFetcher::new(cached_fetcher, dir.clone())?),
+        (None, Some(dir)) => Arc::new(ReplayFetcher::new(dir.clone())),
+        (None, None) => cached_fetcher,
+    };
 
-    let (sender, reciever) = mpsc::channel::<Message>(100);
+    let backend: Arc<dyn SteamBackend> = match network.backend {
+        BackendKind::Html => Arc::new(HtmlBackend::new(fetcher)),
+        BackendKind::Api => match &network.api_key {
+            Some(api_key) => Arc::new(WebApiBackend::with_base_url(
+                fetcher,
+                api_key.clone(),
+                network.api_base_url.clone(),
+            )),
+            None => {
+                return Err(SteamError::boxed_new(
+                    "The api backend needs --api-key or STEAM_API_KEY",
+                ))
+            }
+        },
+    };
+
+    Ok(FetchStack {
+        backend: backend,
+        cache: cache,
+        limiter: limiter,
+    })
+}
+
+const INPUT_HELP: &str = "Commands: p (pause), c (continue), q (quit), s (status), \
+batch <n>, rate <requests per second>, best (best partial path), save (checkpoint), \
+block <steam id>, h (help)";
+
+/// Runs the search alongside the reader for runtime messages.
+async fn run_search(searcher: Searcher, output: OutputConfig, limiter: Arc<RateLimiter>) {
+    let settings = *searcher.settings();
+
+    println!(
+        "Strategy: {:?}, Chunk size: {}, Max depth: {}",
+        settings.strategy, settings.batch_size, settings.max_depth
+    );
+    println!("{}", INPUT_HELP);
+
+    let (sender, reciever) = mpsc::channel::<Request>(settings.message_buffer);
+    let cancel = searcher.cancel_token();
 
     let mut thread_task: JoinSet<()> = JoinSet::new();
 
-    thread_task.spawn(search(searcher, max_depth, chunk_size, reciever));
+    thread_task.spawn(search(searcher, output, reciever));
+
+    thread_task.spawn(get_input(sender, output, limiter, cancel.clone()));
 
-    thread_task.spawn(get_input(sender));
+    // Not joined, since after a signal it keeps waiting for a second one until the program exits.
+    tokio::spawn(cancel_on_signal(cancel));
 
     thread_task.join_all().await;
+}
 
-    //search(searcher, max_depth, chunk_size, reciever).await;
+/// Cancels the search on Ctrl-C or SIGTERM, so it saves a checkpoint and reports what it found.
+/// A second Ctrl-C exits straight away. Returns once the search is cancelled for any reason.
+async fn cancel_on_signal(cancel: CancellationToken) {
+    let terminate = async {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
 
-    Ok(())
+            match signal(SignalKind::terminate()) {
+                Ok(mut terminate) => {
+                    terminate.recv().await;
+                }
+                Err(_) => std::future::pending::<()>().await,
+            }
+        }
+
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => println!("Interrupted, stopping the search..."),
+        _ = terminate => println!("Terminated, stopping the search..."),
+        _ = cancel.cancelled() => return,
+    }
+
+    cancel.cancel();
+
+    if tokio::signal::ctrl_c().await.is_ok() {
+        std::process::exit(130);
+    }
 }
 
-async fn get_input(sender: Sender<Message>) {
-    use std::io::stdin;
+/// What a line typed while searching asks for. The rate limit is changed here rather than by the
+/// search, since it belongs to the fetchers, and quitting cancels the search directly so fetches
+/// in flight are dropped.
+enum Input {
+    Send(Message),
+    Rate(f64),
+    Quit,
+    Help,
+}
 
-    let input_reader = stdin();
+fn parse_input(input: &str) -> Result<Input, String> {
+    let mut words = input.split_whitespace();
+    let command = words.next().unwrap_or("");
+    let argument = words.next();
 
-    loop {
-        let mut input = String::new();
+    let message = match (command, argument) {
+        ("p" | "pause", None) => Message::Pause,
+        ("c" | "continue", None) => Message::Continue,
+        ("q" | "quit", None) => return Ok(Input::Quit),
+        ("s" | "status", None) => Message::Status,
+        ("best", None) => Message::BestPath,
+        ("save", None) => Message::Checkpoint,
+        ("batch", Some(value)) => match value.parse::<usize>() {
+            Ok(batch_size) if batch_size >= 1 => Message::BatchSize(batch_size),
+            _ => return Err(format!("{} is not a batch size", value)),
+        },
+        ("rate", Some(value)) => match value.parse::<f64>() {
+            Ok(rate) if rate > 0_f64 && rate.is_finite() => return Ok(Input::Rate(rate)),
+            _ => return Err(format!("{} is not a rate", value)),
+        },
+        ("block", Some(value)) => match SteamId::parse(value) {
+            Ok(steam_id) => Message::Blacklist(steam_id),
+            Err(err) => return Err(err.to_string()),
+        },
+        ("h" | "help", None) => return Ok(Input::Help),
+        _ => return Err(format!("Unknown command: {}", input)),
+    };
+
+    Ok(Input::Send(message))
+}
+
+/// Reads stdin on a thread of its own. A blocking read would hold up a runtime worker, and the
+/// runtime waits for its blocking tasks on shutdown, so it could not exit until Enter was pressed.
+fn spawn_line_reader() -> UnboundedReceiver<String> {
+    let (sender, reciever) = mpsc::unbounded_channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
 
-        let _ = input_reader.read_line(&mut input);
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
 
-        println!("Read input: {}", input);
+    reciever
+}
 
-        let message = match input.trim() {
-            "q" => Message::Quit,
-            "p" => Message::Pause,
-            "c" => Message::Continue,
-            _ => Message::None,
+/// Sends typed commands to the search until stdin closes or the search is cancelled or over.
+async fn get_input(
+    sender: Sender<Request>,
+    output: OutputConfig,
+    limiter: Arc<RateLimiter>,
+    cancel: CancellationToken,
+) {
+    let mut lines = spawn_line_reader();
+
+    loop {
+        let line = tokio::select! {
+            line = lines.recv() => line,
+            _ = cancel.cancelled() => break,
         };
 
-        println!("Sending: {:?}", message);
+        let Some(input) = line else {
+            break;
+        };
 
-        match sender.send(message.clone()).await {
-            Err(err) => {
-                println!("{}", err.to_string());
-                println!("Exiting...");
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        let message = match parse_input(input.trim()) {
+            Ok(Input::Send(message)) => message,
+            Ok(Input::Rate(rate)) => {
+                limiter.set_rate(rate);
+                println!("Rate limit set to {} requests per second", rate);
+                continue;
+            }
+            Ok(Input::Quit) => {
+                println!("Stopping the search...");
+                cancel.cancel();
                 break;
             }
-            _ => {}
+            Ok(Input::Help) => {
+                println!("{}", INPUT_HELP);
+                continue;
+            }
+            Err(err) => {
+                println!("{}", err);
+                println!("{}", INPUT_HELP);
+                continue;
+            }
+        };
+
+        let (request, reply) = Request::new(message);
+
+        if let Err(err) = sender.send(request).await {
+            println!("{}", err.to_string());
+            println!("Exiting...");
+            break;
         }
 
-        match message {
-            Message::Quit => {
+        // The search answers between batches, so this waits for the one running to finish.
+        let response = tokio::select! {
+            response = reply => response,
+            _ = cancel.cancelled() => break,
+        };
+
+        match response {
+            Ok(response) => print_response(&response, &output),
+            Err(_) => {
+                println!("The search finished before replying");
                 break;
             }
-            _ => {}
         }
     }
 }
 
-async fn search(
-    searcher: Searcher,
-    max_depth: usize,
-    chunk_size: usize,
-    reciever: Receiver<Message>,
-) {
-    let path_result = searcher.start_search(max_depth, chunk_size, reciever).await;
+fn print_response(response: &Response, output: &OutputConfig) {
+    match response {
+        Response::Done(msg) => println!("{}", msg),
+        Response::Rejected(msg) => println!("Rejected: {}", msg),
+        Response::Status(status) => {
+            println!(
+                "{:?}{}: {} iterations, batch size {}, {} visited, {} waiting, {} hops deep",
+                status.strategy,
+                if status.paused { " (paused)" } else { "" },
+                status.iterations,
+                status.batch_size,
+                status.visited,
+                status.frontier,
+                status.depth
+            );
+            println!(
+                "Targets found: {}, remaining: {}, Failed accounts: {}",
+                status.found, status.remaining, status.failures
+            );
 
-    match path_result {
-        Ok(mut path) => {
-            let last = path.pop().unwrap();
+            let label = match status.strategy {
+                Strategy::AStar => "cost",
+                _ => "score",
+            };
 
-            for val in path {
-                print!("{} <-> ", val);
+            for candidate in status.best.iter() {
+                println!(
+                    "  {} ({} hops, {} {})",
+                    output.id_format.format(&candidate.steam_id),
+                    candidate.hops,
+                    label,
+                    candidate.value
+                );
             }
+        }
+        Response::Path(Some(path)) => {
+            let accounts = path
+                .iter()
+                .map(|steam_id| output.id_format.format(steam_id))
+                .collect::<Vec<String>>();
 
-            print!("{}\n", last);
+            println!("Best partial path: {}", accounts.join(" <-> "));
+        }
+        Response::Path(None) => println!("No accounts are waiting to be expanded"),
+    }
+}
+
+fn print_failures(failures: &searcher::Failures, output: &OutputConfig) {
+    if !output.show_failures {
+        return;
+    }
+
+    for (steam_id, reason) in failures.iter() {
+        println!("Failed: {} ({})", output.id_format.format(steam_id), reason);
+    }
+}
+
+async fn search(searcher: Searcher, output: OutputConfig, reciever: Receiver<Request>) {
+    let (found_sender, mut found_reciever) = mpsc::unbounded_channel::<TargetResult>();
+
+    let printer = tokio::spawn(async move {
+        while let Some(result) = found_reciever.recv().await {
+            print_target_result(&result, &output);
+        }
+    });
+
+    // Errors are not Send, so keep only their message while the printer is awaited.
+    let path_result = searcher
+        .start_search(reciever, found_sender)
+        .await
+        .map_err(|err| err.to_string());
+
+    // Lets the input reader and signal handler stop too.
+    searcher.cancel_token().cancel();
+
+    let _ = printer.await;
+
+    match path_result {
+        Ok(report) => {
+            print_failures(&report.failures, &output);
+
+            for result in report.results.iter() {
+                if !matches!(result.outcome, SearchOutcome::Found(_)) {
+                    print_target_result(result, &output);
+                }
+            }
         }
         Err(err) => {
-            println!("{:?}", err);
+            println!("{}", err);
+        }
+    }
+}
+
+fn print_target_result(result: &TargetResult, output: &OutputConfig) {
+    let target = output.id_format.format(&result.target);
+
+    match &result.outcome {
+        SearchOutcome::Found(path) => {
+            let accounts = path
+                .iter()
+                .map(|steam_id| output.id_format.format(steam_id))
+                .collect::<Vec<String>>();
+
+            println!("Path to {}: {}", target, accounts.join(" <-> "));
+
+            if result.optimal {
+                println!("Path is guaranteed to be a shortest one");
+            } else {
+                println!("Path is not guaranteed to be a shortest one");
+            }
+
+            for (index, ranked) in result.alternatives.iter().enumerate() {
+                let accounts = ranked
+                    .path
+                    .iter()
+                    .map(|steam_id| output.id_format.format(steam_id))
+                    .collect::<Vec<String>>();
+
+                println!(
+                    "Path {} ({} hops, score {}): {}",
+                    index + 1,
+                    ranked.hops(),
+                    ranked.score,
+                    accounts.join(" <-> ")
+                );
+            }
+        }
+        SearchOutcome::NotFound { max_depth } => {
+            println!("No path to {} within {} hops", target, max_depth);
+        }
+        SearchOutcome::FrontierExhausted { max_frontier } => {
+            println!(
+                "No path to {} among the accounts kept within the frontier limit of {}",
+                target, max_frontier
+            );
+        }
+        SearchOutcome::Cancelled => {
+            println!("Search stopped before reaching {}", target);
         }
     }
 }
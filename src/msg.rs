@@ -1,7 +1,81 @@
+use tokio::sync::oneshot;
+
+use crate::cli::Strategy;
+use crate::steam_id::SteamId;
+
 #[derive(Debug, Clone)]
 pub enum Message {
-    None,
     Pause,
     Continue,
-    Quit,
+    /// Report how far the search has got.
+    Status,
+    /// Expand this many accounts per iteration from now on.
+    BatchSize(usize),
+    /// Report the path to the most promising account not expanded yet.
+    BestPath,
+    /// Save a checkpoint now rather than waiting for the next scheduled one.
+    Checkpoint,
+    /// Never expand this account, nor reach anyone else through it.
+    Blacklist(SteamId),
+}
+
+/// A message for the running search, with where to send its reply.
+#[derive(Debug)]
+pub struct Request {
+    pub message: Message,
+    pub reply: oneshot::Sender<Response>,
+}
+
+impl Request {
+    pub fn new(message: Message) -> (Self, oneshot::Receiver<Response>) {
+        let (reply, reciever) = oneshot::channel();
+
+        (
+            Request {
+                message: message,
+                reply: reply,
+            },
+            reciever,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Response {
+    /// The message was applied, and what changed.
+    Done(String),
+    /// The message could not be applied, and why.
+    Rejected(String),
+    Status(Status),
+    /// Accounts from the most promising candidate back to the source, if there is one.
+    Path(Option<Vec<SteamId>>),
+}
+
+/// How far a search has got, as sent in reply to `Message::Status`.
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub strategy: Strategy,
+    pub paused: bool,
+    pub iterations: usize,
+    pub batch_size: usize,
+    /// Accounts reached so far.
+    pub visited: usize,
+    /// Accounts waiting to be expanded.
+    pub frontier: usize,
+    /// Most hops from the source (or from any target, searching bidirectionally) reached so far.
+    pub depth: usize,
+    pub found: usize,
+    pub remaining: usize,
+    pub failures: usize,
+    /// Best accounts waiting to be expanded, best first. Empty for strategies that do not rank
+    /// accounts.
+    pub best: Vec<Candidate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub steam_id: SteamId,
+    pub hops: usize,
+    /// Similarity score for the greedy strategy, estimated path cost for astar.
+    pub value: f32,
 }
@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::fetcher::{FetchFuture, PageFetcher};
+use crate::fixtures::{fixture_path, write_page};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Serve fresh entries from disk and store everything fetched.
+    Normal,
+    /// Ignore existing entries but overwrite them with what is fetched.
+    Refresh,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// Disk backed page cache keyed by URL, with entries expiring after `ttl`.
+pub struct CachedFetcher {
+    inner: Arc<dyn PageFetcher>,
+    dir: PathBuf,
+    ttl: Duration,
+    mode: CacheMode,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl CachedFetcher {
+    pub fn new(
+        inner: Arc<dyn PageFetcher>,
+        dir: PathBuf,
+        ttl: Duration,
+        mode: CacheMode,
+    ) -> Result<Self, std::io::Error> {
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(CachedFetcher {
+            inner: inner,
+            dir: dir,
+            ttl: ttl,
+            mode: mode,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn read_fresh(&self, path: &Path) -> Option<String> {
+        if self.mode == CacheMode::Refresh {
+            return None;
+        }
+
+        let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or(Duration::ZERO);
+
+        if age > self.ttl {
+            return None;
+        }
+
+        tokio::fs::read_to_string(path).await.ok()
+    }
+}
+
+impl PageFetcher for CachedFetcher {
+    fn fetch(&self, url: String) -> FetchFuture<'_> {
+        Box::pin(async move {
+            let path = fixture_path(&self.dir, &url);
+
+            if let Some(page) = self.read_fresh(&path).await {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(page);
+            }
+
+            self.misses.fetch_add(1, Ordering::Relaxed);
+
+            let page = self.inner.fetch(url).await?;
+
+            // A failed write only costs a future cache miss, so it is not worth failing the fetch.
+            let _ = write_page(&path, &page).await;
+
+            Ok(page)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::MemoryFetcher;
+
+    #[tokio::test]
+    async fn concurrent_misses_leave_one_whole_page() {
+        let dir = std::env::temp_dir().join(format!("steam_cache_{}", std::process::id()));
+        let url = String::from("https://steamcommunity.com/profiles/76561197960265729");
+        let page = "x".repeat(1 << 20);
+
+        let mut pages = MemoryFetcher::new();
+        pages.insert(url.clone(), page.clone());
+
+        let cache = Arc::new(
+            CachedFetcher::new(
+                Arc::new(pages),
+                dir.clone(),
+                Duration::from_secs(60),
+                CacheMode::Refresh,
+            )
+            .unwrap(),
+        );
+
+        let mut fetches = tokio::task::JoinSet::new();
+
+        for _ in 0..16 {
+            let cache = Arc::clone(&cache);
+            let url = url.clone();
+
+            fetches.spawn(async move { cache.fetch(url).await.unwrap() });
+        }
+
+        fetches.join_all().await;
+
+        let cached = std::fs::read_to_string(fixture_path(&dir, &url));
+        let leftovers = std::fs::read_dir(&dir).unwrap().count();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(cached.unwrap(), page);
+        assert_eq!(leftovers, 1);
+    }
+}
@@ -0,0 +1,555 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::checkpoint::nan_map_as_null;
+use crate::steam_id::SteamId;
+
+/// A path between two accounts with the summed scores of the accounts along it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedPath {
+    pub path: Vec<SteamId>,
+    pub score: f32,
+}
+
+impl RankedPath {
+    pub fn hops(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    /// Shorter paths first, then paths through accounts more similar to the target.
+    fn rank(&self, other: &RankedPath) -> Ordering {
+        self.path
+            .len()
+            .cmp(&other.path.len())
+            .then(other.score.total_cmp(&self.score))
+    }
+}
+
+/// Friendships seen while searching, along with the scores of the accounts that were scored.
+/// Friendship is mutual, so every edge is stored in both directions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FriendGraph {
+    adjacency: HashMap<SteamId, HashSet<SteamId>>,
+    #[serde(with = "nan_map_as_null")]
+    scores: HashMap<SteamId, f32>,
+}
+
+impl FriendGraph {
+    pub fn new() -> Self {
+        FriendGraph {
+            adjacency: HashMap::new(),
+            scores: HashMap::new(),
+        }
+    }
+
+    pub fn add_friends(&mut self, person: SteamId, friends: impl IntoIterator<Item = SteamId>) {
+        for friend in friends {
+            self.adjacency.entry(person).or_default().insert(friend);
+            self.adjacency.entry(friend).or_default().insert(person);
+        }
+    }
+
+    pub fn set_score(&mut self, steam_id: SteamId, score: f32) {
+        self.scores.insert(steam_id, score);
+    }
+
+    pub fn score(&self, steam_id: &SteamId) -> Option<f32> {
+        self.scores.get(steam_id).copied()
+    }
+
+    /// Sum of the known scores of the accounts between the two ends of `path`.
+    fn path_score(&self, path: &[SteamId]) -> f32 {
+        if path.len() < 3 {
+            return 0_f32;
+        }
+
+        path[1..path.len() - 1]
+            .iter()
+            .filter_map(|steam_id| self.score(steam_id))
+            .filter(|score| !score.is_nan())
+            .fold(0_f32, |acc, score| acc + score)
+    }
+
+    fn rank(&self, path: Vec<SteamId>) -> RankedPath {
+        RankedPath {
+            score: self.path_score(&path),
+            path: path,
+        }
+    }
+
+    /// Breadth-first shortest path of at most `max_hops`, avoiding the banned accounts and edges.
+    fn shortest_path(
+        &self,
+        from: SteamId,
+        to: SteamId,
+        max_hops: usize,
+        banned_nodes: &HashSet<SteamId>,
+        banned_edges: &HashSet<(SteamId, SteamId)>,
+    ) -> Option<Vec<SteamId>> {
+        let mut preds: HashMap<SteamId, SteamId> = HashMap::new();
+        let mut queue: VecDeque<(SteamId, usize)> = VecDeque::from([(from, 0)]);
+
+        while let Some((cur, hops)) = queue.pop_front() {
+            if cur == to {
+                let mut path = vec![to];
+                let mut node = to;
+
+                while let Some(pred) = preds.get(&node) {
+                    path.push(*pred);
+                    node = *pred;
+                }
+
+                path.reverse();
+                return Some(path);
+            }
+
+            if hops >= max_hops {
+                continue;
+            }
+
+            for next in self.adjacency.get(&cur).into_iter().flatten() {
+                if *next == from
+                    || preds.contains_key(next)
+                    || banned_nodes.contains(next)
+                    || banned_edges.contains(&(cur, *next))
+                {
+                    continue;
+                }
+
+                preds.insert(*next, cur);
+                queue.push_back((*next, hops + 1));
+            }
+        }
+
+        None
+    }
+
+    /// Up to `k` loopless paths of at most `max_hops` using Yen's algorithm, shortest first and
+    /// then by score.
+    pub fn k_shortest_paths(
+        &self,
+        from: SteamId,
+        to: SteamId,
+        k: usize,
+        max_hops: usize,
+    ) -> Vec<RankedPath> {
+        let Some(first) = self.shortest_path(from, to, max_hops, &HashSet::new(), &HashSet::new())
+        else {
+            return Vec::new();
+        };
+
+        let mut found: Vec<RankedPath> = vec![self.rank(first)];
+        let mut candidates: Vec<RankedPath> = Vec::new();
+
+        while found.len() < k {
+            let last = found.last().unwrap().path.clone();
+
+            for spur_index in 0..last.len() - 1 {
+                let spur = last[spur_index];
+                let root = &last[..spur_index];
+
+                let mut banned_edges: HashSet<(SteamId, SteamId)> = HashSet::new();
+
+                for ranked in found.iter() {
+                    let path = &ranked.path;
+
+                    // Paths sharing the root up to and including the spur can't leave it the same way.
+                    if path.len() > spur_index + 1 && path[..=spur_index] == last[..=spur_index] {
+                        banned_edges.insert((path[spur_index], path[spur_index + 1]));
+                    }
+                }
+
+                let banned_nodes = root.iter().copied().collect::<HashSet<SteamId>>();
+
+                let Some(spur_path) = self.shortest_path(
+                    spur,
+                    to,
+                    max_hops - spur_index,
+                    &banned_nodes,
+                    &banned_edges,
+                ) else {
+                    continue;
+                };
+
+                let mut path = root.to_vec();
+                path.extend(spur_path);
+
+                let is_new = found
+                    .iter()
+                    .chain(candidates.iter())
+                    .all(|ranked| ranked.path != path);
+
+                if is_new {
+                    candidates.push(self.rank(path));
+                }
+            }
+
+            let Some(best) =
+                (0..candidates.len()).min_by(|a, b| candidates[*a].rank(&candidates[*b]))
+            else {
+                break;
+            };
+
+            found.push(candidates.swap_remove(best));
+        }
+
+        found.sort_by(|a, b| a.rank(b));
+        found
+    }
+
+    /// Hops from `from` to every account reachable within `max_hops`.
+    fn distances(&self, from: SteamId, max_hops: usize) -> HashMap<SteamId, usize> {
+        let mut hops: HashMap<SteamId, usize> = HashMap::from([(from, 0)]);
+        let mut queue: VecDeque<SteamId> = VecDeque::from([from]);
+
+        while let Some(cur) = queue.pop_front() {
+            let next_hops = hops[&cur] + 1;
+
+            if next_hops > max_hops {
+                continue;
+            }
+
+            for next in self.adjacency.get(&cur).into_iter().flatten() {
+                if !hops.contains_key(next) {
+                    hops.insert(*next, next_hops);
+                    queue.push_back(*next);
+                }
+            }
+        }
+
+        hops
+    }
+
+    /// Up to `k` paths of at most `max_hops` that share no accounts besides their ends, shortest
+    /// first and then by score.
+    ///
+    /// Every account is split into an entry and an exit joined by a single unit of capacity, so
+    /// a flow of `n` units is `n` account-disjoint paths. Paths are added one shortest
+    /// augmenting path at a time, which can reroute earlier paths to make room for later ones.
+    /// Each flow has the fewest total hops for its number of paths, and the one with the most
+    /// paths that fit in `max_hops` is kept.
+    pub fn disjoint_paths(
+        &self,
+        from: SteamId,
+        to: SteamId,
+        k: usize,
+        max_hops: usize,
+    ) -> Vec<RankedPath> {
+        if from == to {
+            return vec![self.rank(vec![from])];
+        }
+
+        // Only accounts that fit on some path within `max_hops` can be used.
+        let from_hops = self.distances(from, max_hops);
+        let to_hops = self.distances(to, max_hops);
+
+        let nodes = from_hops
+            .iter()
+            .filter(|(steam_id, hops)| {
+                to_hops
+                    .get(steam_id)
+                    .is_some_and(|to_hops| **hops + to_hops <= max_hops)
+            })
+            .map(|(steam_id, _)| *steam_id)
+            .collect::<Vec<SteamId>>();
+
+        if nodes.is_empty() {
+            return Vec::new();
+        }
+
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, steam_id)| (*steam_id, index))
+            .collect::<HashMap<SteamId, usize>>();
+
+        let mut network = FlowNetwork::new(nodes.len() * 2);
+
+        for (steam_id, i) in index.iter() {
+            if *steam_id != from && *steam_id != to {
+                network.add_edge(2 * i, 2 * i + 1, 0);
+            }
+
+            for friend in self.adjacency[steam_id].iter() {
+                if let Some(j) = index.get(friend) {
+                    network.add_edge(2 * i + 1, 2 * j, 1);
+                }
+            }
+        }
+
+        let (source, sink) = (2 * index[&from] + 1, 2 * index[&to]);
+        let mut best: Vec<RankedPath> = Vec::new();
+
+        while best.len() < k && network.augment(source, sink) {
+            let paths = network
+                .paths(source, sink)
+                .into_iter()
+                .map(|path| {
+                    std::iter::once(from)
+                        .chain(path.into_iter().map(|node| nodes[node / 2]))
+                        .collect::<Vec<SteamId>>()
+                })
+                .filter(|path| path.len() - 1 <= max_hops)
+                .collect::<Vec<Vec<SteamId>>>();
+
+            if paths.len() > best.len() {
+                best = paths.into_iter().map(|path| self.rank(path)).collect();
+            }
+        }
+
+        best.sort_by(|a, b| a.rank(b));
+        best
+    }
+}
+
+/// Unit capacity edge of a `FlowNetwork`. Edges are stored in pairs, so the reverse of edge `i`
+/// is edge `i ^ 1`.
+struct FlowEdge {
+    to: usize,
+    capacity: i32,
+    cost: i32,
+}
+
+/// Residual network for finding account-disjoint paths with min-cost flow.
+struct FlowNetwork {
+    edges: Vec<FlowEdge>,
+    outgoing: Vec<Vec<usize>>,
+}
+
+impl FlowNetwork {
+    fn new(size: usize) -> Self {
+        FlowNetwork {
+            edges: Vec::new(),
+            outgoing: vec![Vec::new(); size],
+        }
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, cost: i32) {
+        self.outgoing[from].push(self.edges.len());
+        self.edges.push(FlowEdge {
+            to: to,
+            capacity: 1,
+            cost: cost,
+        });
+
+        self.outgoing[to].push(self.edges.len());
+        self.edges.push(FlowEdge {
+            to: from,
+            capacity: 0,
+            cost: -cost,
+        });
+    }
+
+    /// Pushes one more unit along the cheapest residual path, returning whether there was one.
+    /// Reverse edges have negative costs, so this uses Bellman-Ford rather than Dijkstra.
+    fn augment(&mut self, source: usize, sink: usize) -> bool {
+        let mut cost: Vec<Option<i32>> = vec![None; self.outgoing.len()];
+        let mut via: Vec<Option<usize>> = vec![None; self.outgoing.len()];
+        let mut queued = vec![false; self.outgoing.len()];
+        let mut queue: VecDeque<usize> = VecDeque::from([source]);
+
+        cost[source] = Some(0);
+
+        while let Some(cur) = queue.pop_front() {
+            queued[cur] = false;
+
+            for edge_index in self.outgoing[cur].iter() {
+                let edge = &self.edges[*edge_index];
+                let next_cost = cost[cur].unwrap() + edge.cost;
+
+                if edge.capacity == 0 || cost[edge.to].is_some_and(|cost| cost <= next_cost) {
+                    continue;
+                }
+
+                cost[edge.to] = Some(next_cost);
+                via[edge.to] = Some(*edge_index);
+
+                if !queued[edge.to] {
+                    queued[edge.to] = true;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        if cost[sink].is_none() {
+            return false;
+        }
+
+        let mut node = sink;
+
+        while let Some(edge_index) = via[node] {
+            self.edges[edge_index].capacity -= 1;
+            self.edges[edge_index ^ 1].capacity += 1;
+            node = self.edges[edge_index ^ 1].to;
+
+            if node == source {
+                break;
+            }
+        }
+
+        true
+    }
+
+    /// Splits the current flow into paths, as the nodes visited after `source`.
+    fn paths(&self, source: usize, sink: usize) -> Vec<Vec<usize>> {
+        // A forward edge carries flow once its unit of capacity has moved to its reverse.
+        let mut flow = self
+            .edges
+            .iter()
+            .enumerate()
+            .map(|(index, edge)| index.is_multiple_of(2) && edge.capacity == 0)
+            .collect::<Vec<bool>>();
+
+        let mut paths = Vec::new();
+
+        loop {
+            let mut path = Vec::new();
+            let mut node = source;
+
+            while node != sink {
+                let Some(edge_index) = self.outgoing[node]
+                    .iter()
+                    .copied()
+                    .find(|edge_index| flow[*edge_index])
+                else {
+                    break;
+                };
+
+                flow[edge_index] = false;
+                node = self.edges[edge_index].to;
+
+                // Entry and exit of the same account only count once.
+                if node.is_multiple_of(2) {
+                    path.push(node);
+                }
+            }
+
+            if node != sink {
+                return paths;
+            }
+
+            paths.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(account_id: u32) -> SteamId {
+        SteamId::from_account_id(account_id)
+    }
+
+    fn graph(edges: &[(u32, u32)]) -> FriendGraph {
+        let mut graph = FriendGraph::new();
+
+        for (a, b) in edges {
+            graph.add_friends(id(*a), [id(*b)]);
+        }
+
+        graph
+    }
+
+    fn paths_of(ranked: &[RankedPath]) -> Vec<Vec<SteamId>> {
+        ranked.iter().map(|ranked| ranked.path.clone()).collect()
+    }
+
+    #[test]
+    fn finds_disjoint_paths_the_shortest_one_blocks() {
+        // 1-2-3-6 is the only shortest path, but taking it leaves no second path, while
+        // 1-2-7-8-6 and 1-4-5-3-6 share nothing.
+        let graph = graph(&[
+            (1, 2),
+            (2, 3),
+            (3, 6),
+            (2, 7),
+            (7, 8),
+            (8, 6),
+            (1, 4),
+            (4, 5),
+            (5, 3),
+        ]);
+
+        let mut paths = paths_of(&graph.disjoint_paths(id(1), id(6), 2, 6));
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                vec![id(1), id(2), id(7), id(8), id(6)],
+                vec![id(1), id(4), id(5), id(3), id(6)],
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_paths_within_max_hops() {
+        // 1-2-6 fits in two hops, 1-3-4-5-6 does not.
+        let graph = graph(&[(1, 2), (2, 6), (1, 3), (3, 4), (4, 5), (5, 6)]);
+
+        assert_eq!(
+            paths_of(&graph.disjoint_paths(id(1), id(6), 2, 2)),
+            vec![vec![id(1), id(2), id(6)]]
+        );
+        assert_eq!(graph.disjoint_paths(id(1), id(6), 2, 4).len(), 2);
+    }
+
+    #[test]
+    fn direct_friendship_is_one_path() {
+        let graph = graph(&[(1, 2), (1, 3), (3, 2)]);
+
+        assert_eq!(
+            paths_of(&graph.disjoint_paths(id(1), id(2), 3, 4)),
+            vec![vec![id(1), id(2)], vec![id(1), id(3), id(2)]]
+        );
+    }
+
+    #[test]
+    fn stops_at_k_paths() {
+        let graph = graph(&[(1, 2), (2, 6), (1, 3), (3, 6), (1, 4), (4, 6)]);
+
+        assert_eq!(graph.disjoint_paths(id(1), id(6), 2, 4).len(), 2);
+        assert!(graph.disjoint_paths(id(1), id(7), 2, 4).is_empty());
+    }
+
+    #[test]
+    fn finds_every_loopless_path_in_order() {
+        // 1-2-4-3-5 leaves 2 the same way as 1-2-4-5, so it is only found if that path's edge
+        // out of 4 is banned when spurring from 4, not from 2.
+        let graph = graph(&[(1, 2), (2, 3), (3, 5), (2, 4), (4, 5), (4, 3)]);
+
+        let ranked = graph.k_shortest_paths(id(1), id(5), 10, 10);
+        let lengths = ranked
+            .iter()
+            .map(|ranked| ranked.path.len())
+            .collect::<Vec<usize>>();
+
+        let mut paths = paths_of(&ranked);
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                vec![id(1), id(2), id(3), id(4), id(5)],
+                vec![id(1), id(2), id(3), id(5)],
+                vec![id(1), id(2), id(4), id(3), id(5)],
+                vec![id(1), id(2), id(4), id(5)],
+            ]
+        );
+        assert_eq!(lengths, vec![4, 4, 5, 5]);
+    }
+
+    #[test]
+    fn shortest_paths_stop_at_k_and_max_hops() {
+        let graph = graph(&[(1, 2), (2, 3), (3, 5), (2, 4), (4, 5), (4, 3)]);
+
+        assert_eq!(graph.k_shortest_paths(id(1), id(5), 1, 10).len(), 1);
+        assert_eq!(graph.k_shortest_paths(id(1), id(5), 3, 10).len(), 3);
+        assert_eq!(graph.k_shortest_paths(id(1), id(5), 10, 3).len(), 2);
+        assert!(graph.k_shortest_paths(id(1), id(5), 10, 2).is_empty());
+        assert!(graph.k_shortest_paths(id(1), id(6), 10, 10).is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::fetcher::{FetchFuture, PageFetcher};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+    pub max_in_flight: usize,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            per_second: 10_f64,
+            burst: 20,
+            max_in_flight: 32,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    per_second: f64,
+    burst: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+        self.last_refill = now;
+    }
+
+    /// Takes a token if one is available, otherwise returns how long until one will be.
+    fn try_take(&mut self) -> Result<(), Duration> {
+        self.refill();
+
+        if self.tokens >= 1_f64 {
+            self.tokens -= 1_f64;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1_f64 - self.tokens) / self.per_second,
+            ))
+        }
+    }
+}
+
+/// Token bucket shared by every outbound request, plus a cap on how many may be in flight at once.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+    in_flight: Semaphore,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            bucket: Mutex::new(Bucket {
+                tokens: limit.burst as f64,
+                per_second: limit.per_second,
+                burst: limit.burst as f64,
+                last_refill: Instant::now(),
+            }),
+            in_flight: Semaphore::new(limit.max_in_flight),
+        }
+    }
+
+    /// Changes the sustained rate from now on. Tokens already in the bucket are kept.
+    pub fn set_rate(&self, per_second: f64) {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        bucket.refill();
+        bucket.per_second = per_second;
+    }
+
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self
+            .in_flight
+            .acquire()
+            .await
+            .expect("Rate limiter semaphore is never closed");
+
+        loop {
+            let wait = match self.bucket.lock().unwrap().try_take() {
+                Ok(()) => break,
+                Err(wait) => wait,
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+
+        permit
+    }
+}
+
+/// Sends every fetch of the inner fetcher through a shared `RateLimiter`.
+pub struct ThrottledFetcher {
+    inner: Arc<dyn PageFetcher>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl ThrottledFetcher {
+    pub fn new(inner: Arc<dyn PageFetcher>, limiter: Arc<RateLimiter>) -> Self {
+        ThrottledFetcher {
+            inner: inner,
+            limiter: limiter,
+        }
+    }
+}
+
+impl PageFetcher for ThrottledFetcher {
+    fn fetch(&self, url: String) -> FetchFuture<'_> {
+        Box::pin(async move {
+            let _permit = self.limiter.acquire().await;
+
+            self.inner.fetch(url).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_second: f64, burst: u32, max_in_flight: usize) -> RateLimiter {
+        RateLimiter::new(RateLimit {
+            per_second: per_second,
+            burst: burst,
+            max_in_flight: max_in_flight,
+        })
+    }
+
+    #[test]
+    fn bucket_allows_a_burst_then_waits_for_a_token() {
+        let limiter = limiter(2_f64, 3, 8);
+        let mut bucket = limiter.bucket.lock().unwrap();
+
+        for _ in 0..3 {
+            assert!(bucket.try_take().is_ok());
+        }
+
+        let wait = bucket.try_take().unwrap_err();
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn set_rate_changes_the_wait() {
+        let limiter = limiter(1_f64, 1, 8);
+        limiter.bucket.lock().unwrap().try_take().unwrap();
+
+        limiter.set_rate(100_f64);
+
+        let wait = limiter.bucket.lock().unwrap().try_take().unwrap_err();
+        assert!(wait <= Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn acquire_caps_requests_in_flight() {
+        let limiter = limiter(1000_f64, 100, 2);
+
+        let first = limiter.acquire().await;
+        let _second = limiter.acquire().await;
+
+        let third = tokio::time::timeout(Duration::from_millis(50), limiter.acquire()).await;
+        assert!(third.is_err());
+
+        drop(first);
+
+        let third = tokio::time::timeout(Duration::from_millis(50), limiter.acquire()).await;
+        assert!(third.is_ok());
+    }
+}
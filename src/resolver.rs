@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::OnceCell;
+
+use crate::error::SteamIdError;
+use crate::steam_id::SteamId;
+
+const COMMUNITY_BASE: &str = "https://steamcommunity.com";
+
+/// Rewrites any form of profile link (or a bare vanity name) to
+/// `https://steamcommunity.com/{id|profiles}/<value>`, dropping trailing paths like `/friends/`.
+/// Vanity names are case-insensitive on Steam, so they are lowercased.
+pub fn canonical_link(link: &str) -> Result<String, SteamIdError> {
+    let trimmed = link.trim().trim_end_matches('/');
+
+    if let Ok(steam_id) = SteamId::parse(trimmed) {
+        return Ok(steam_id.profile_url());
+    }
+
+    if !trimmed.contains('/') {
+        if trimmed.is_empty() || trimmed.contains(char::is_whitespace) {
+            return Err(SteamIdError::Invalid(link.to_string()));
+        }
+
+        return Ok(format!("{}/id/{}", COMMUNITY_BASE, trimmed.to_lowercase()));
+    }
+
+    let with_scheme = if trimmed.contains("://") {
+        trimmed.to_string()
+    } else {
+        format!("https://{}", trimmed)
+    };
+
+    let url =
+        reqwest::Url::parse(&with_scheme).map_err(|_| SteamIdError::Invalid(link.to_string()))?;
+    let host = url.host_str().unwrap_or("").trim_start_matches("www.");
+
+    if host != "steamcommunity.com" {
+        return Err(SteamIdError::Invalid(link.to_string()));
+    }
+
+    let segments = url
+        .path_segments()
+        .map(|segments| segments.collect::<Vec<&str>>())
+        .unwrap_or_default();
+
+    match (segments.first(), segments.get(1)) {
+        (Some(&"id"), Some(vanity)) if !vanity.is_empty() => {
+            Ok(format!("{}/id/{}", COMMUNITY_BASE, vanity.to_lowercase()))
+        }
+        _ => Err(SteamIdError::Invalid(link.to_string())),
+    }
+}
+
+/// Remembers which SteamID each canonical link resolved to, so vanity names are looked up once,
+/// even when several callers ask for the same one at the same time. Failed lookups are not kept.
+#[derive(Debug, Default)]
+pub struct ResolutionCache {
+    resolved: Mutex<HashMap<String, Arc<OnceCell<SteamId>>>>,
+}
+
+impl ResolutionCache {
+    pub fn new() -> Self {
+        ResolutionCache {
+            resolved: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Canonicalizes `link` and resolves it, calling `lookup` with the canonical link only when
+    /// it holds a vanity name that has not been resolved before.
+    pub async fn resolve<F, Fut>(&self, link: &str, lookup: F) -> Result<SteamId, Box<dyn Error>>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<SteamId, Box<dyn Error>>>,
+    {
+        let canonical = canonical_link(link)?;
+
+        if let Ok(steam_id) = SteamId::parse(&canonical) {
+            return Ok(steam_id);
+        }
+
+        let cell = Arc::clone(
+            self.resolved
+                .lock()
+                .unwrap()
+                .entry(canonical.clone())
+                .or_default(),
+        );
+
+        let steam_id = cell.get_or_try_init(|| lookup(canonical)).await?;
+
+        Ok(*steam_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::error::SteamError;
+
+    const GABEN: &str = "https://steamcommunity.com/id/gaben";
+
+    #[test]
+    fn vanity_links_share_one_form() {
+        for link in [
+            "https://steamcommunity.com/id/GabeN",
+            "https://www.steamcommunity.com/id/gaben/",
+            "http://steamcommunity.com/id/gaben/friends/",
+            "steamcommunity.com/id/gaben",
+            "www.steamcommunity.com/id/gaben/games/?tab=all",
+            "  GabeN  ",
+        ] {
+            assert_eq!(canonical_link(link), Ok(String::from(GABEN)), "{}", link);
+        }
+    }
+
+    #[test]
+    fn profile_links_become_profile_urls() {
+        let profile = "https://steamcommunity.com/profiles/76561197960287930";
+
+        for link in [
+            "76561197960287930",
+            "STEAM_0:0:11101",
+            "[U:1:22202]",
+            "https://steamcommunity.com/profiles/76561197960287930/friends/",
+        ] {
+            assert_eq!(canonical_link(link), Ok(String::from(profile)), "{}", link);
+        }
+    }
+
+    #[test]
+    fn rejects_links_elsewhere() {
+        for link in [
+            "",
+            "gabe newell",
+            "https://example.com/id/gaben",
+            "https://steamcommunity.com.example.com/id/gaben",
+            "https://steamcommunity.com/groups/valve",
+            "https://steamcommunity.com/id/",
+        ] {
+            assert!(
+                matches!(canonical_link(link), Err(SteamIdError::Invalid(_))),
+                "{}",
+                link
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn looks_up_each_vanity_name_once() {
+        let cache = ResolutionCache::new();
+        let lookups = AtomicUsize::new(0);
+        let steam_id = SteamId::from_account_id(22202);
+
+        let lookup = |canonical: String| {
+            lookups.fetch_add(1, Ordering::Relaxed);
+            assert_eq!(canonical, GABEN);
+
+            async move {
+                tokio::task::yield_now().await;
+                Ok::<_, Box<dyn Error>>(steam_id)
+            }
+        };
+
+        let (first, second) = tokio::join!(
+            cache.resolve("https://steamcommunity.com/id/GabeN/", lookup),
+            cache.resolve("gaben", lookup)
+        );
+        let third = cache.resolve("steamcommunity.com/id/gaben", lookup).await;
+
+        assert_eq!(first.unwrap(), steam_id);
+        assert_eq!(second.unwrap(), steam_id);
+        assert_eq!(third.unwrap(), steam_id);
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn profile_links_and_failures_are_not_cached() {
+        let cache = ResolutionCache::new();
+        let lookups = AtomicUsize::new(0);
+
+        let failing = |_: String| {
+            lookups.fetch_add(1, Ordering::Relaxed);
+            async { Err::<SteamId, Box<dyn Error>>(SteamError::boxed_new("No match")) }
+        };
+
+        let profile = cache.resolve("[U:1:22202]", failing).await;
+        assert_eq!(profile.unwrap(), SteamId::from_account_id(22202));
+        assert_eq!(lookups.load(Ordering::Relaxed), 0);
+
+        assert!(cache.resolve("gaben", failing).await.is_err());
+        assert!(cache.resolve("gaben", failing).await.is_err());
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::fetcher::{FetchFuture, PageFetcher};
+use crate::util::random_fraction;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter for the given (zero based) failed attempt.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_delay);
+
+        ceiling.mul_f64(random_fraction())
+    }
+}
+
+/// Retries retryable failures of the inner fetcher, honoring `Retry-After` up to `max_delay` when
+/// the server sends one.
+pub struct RetryingFetcher {
+    inner: Arc<dyn PageFetcher>,
+    policy: RetryPolicy,
+}
+
+impl RetryingFetcher {
+    pub fn new(inner: Arc<dyn PageFetcher>, policy: RetryPolicy) -> Self {
+        RetryingFetcher {
+            inner: inner,
+            policy: policy,
+        }
+    }
+}
+
+impl PageFetcher for RetryingFetcher {
+    fn fetch(&self, url: String) -> FetchFuture<'_> {
+        Box::pin(async move {
+            let mut attempt = 0_u32;
+
+            loop {
+                let err = match self.inner.fetch(url.clone()).await {
+                    Ok(page) => return Ok(page),
+                    Err(err) => err,
+                };
+
+                attempt += 1;
+
+                if !err.is_retryable() || attempt >= self.policy.max_attempts {
+                    return Err(err);
+                }
+
+                // A server asking for a longer wait than `max_delay` gets `max_delay`.
+                let delay = err
+                    .retry_after()
+                    .map(|delay| delay.min(self.policy.max_delay))
+                    .unwrap_or_else(|| self.policy.backoff(attempt - 1));
+
+                tokio::time::sleep(delay).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::error::FetchError;
+
+    /// Fails with each status code in turn, then serves a page.
+    struct ScriptedFetcher {
+        codes: Mutex<VecDeque<u16>>,
+        retry_after: Option<Duration>,
+        calls: AtomicU32,
+    }
+
+    impl ScriptedFetcher {
+        fn new(codes: &[u16], retry_after: Option<Duration>) -> Arc<Self> {
+            Arc::new(ScriptedFetcher {
+                codes: Mutex::new(codes.iter().copied().collect()),
+                retry_after: retry_after,
+                calls: AtomicU32::new(0),
+            })
+        }
+    }
+
+    impl PageFetcher for ScriptedFetcher {
+        fn fetch(&self, url: String) -> FetchFuture<'_> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let code = self.codes.lock().unwrap().pop_front();
+            let retry_after = self.retry_after;
+
+            Box::pin(async move {
+                match code {
+                    Some(code) => Err(FetchError::Status {
+                        url: url,
+                        code: code,
+                        retry_after: retry_after,
+                    }),
+                    None => Ok(String::from("page")),
+                }
+            })
+        }
+    }
+
+    fn quick_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn backoff_stays_under_the_capped_exponential() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        };
+
+        for attempt in 0..64 {
+            let ceiling = Duration::from_millis(100 << attempt.min(10)).min(policy.max_delay);
+
+            assert!(policy.backoff(attempt) <= ceiling, "attempt {}", attempt);
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_the_page_comes_back() {
+        let inner = ScriptedFetcher::new(&[503, 429], None);
+        let fetcher = RetryingFetcher::new(inner.clone(), quick_policy(4));
+
+        assert_eq!(fetcher.fetch(String::from("url")).await.unwrap(), "page");
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let inner = ScriptedFetcher::new(&[503, 503, 503, 503], None);
+        let fetcher = RetryingFetcher::new(inner.clone(), quick_policy(3));
+
+        let err = fetcher.fetch(String::from("url")).await.unwrap_err();
+
+        assert!(matches!(err, FetchError::Status { code: 503, .. }));
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_failures() {
+        let inner = ScriptedFetcher::new(&[404], None);
+        let fetcher = RetryingFetcher::new(inner.clone(), quick_policy(4));
+
+        assert!(fetcher.fetch(String::from("url")).await.is_err());
+        assert_eq!(inner.calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_capped_at_max_delay() {
+        let inner = ScriptedFetcher::new(&[429], Some(Duration::from_secs(3600)));
+        let fetcher = RetryingFetcher::new(inner.clone(), quick_policy(2));
+
+        let page = tokio::time::timeout(Duration::from_secs(5), fetcher.fetch(String::from("url")))
+            .await
+            .expect("Retry-After was not capped");
+
+        assert_eq!(page.unwrap(), "page");
+    }
+}
@@ -1,258 +1,2116 @@
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
-use std::future::poll_fn;
+use std::future::{poll_fn, Future};
 use std::sync::mpsc::SendError;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::task::{Context, Poll};
 
-use tokio::sync::mpsc::Receiver;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{Receiver, UnboundedSender};
 use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
 
-use crate::error::SteamError;
-use crate::heap::{MaxHeap, Order};
-use crate::msg::Message;
-use crate::steam_requester::{build_account_info, get_friends, score_account_overlap, AccountInfo};
-use crate::util::{time_fn, time_fn_async, unzip_tuple_lists};
+use crate::backend::{FriendList, SteamBackend};
+use crate::checkpoint::{nan_as_null, Checkpoint, CHECKPOINT_VERSION};
+use crate::cli::{Strategy, TargetScoring};
+use crate::config::{CheckpointConfig, ScoringWeights, SearchConfig};
+use crate::error::{failure_reason, CheckpointError, FetchError, SteamError};
+use crate::heap::{MaxHeap, OrderedF32};
+use crate::msg::{Candidate, Message, Request, Response, Status};
+use crate::paths::{FriendGraph, RankedPath};
+use crate::steam_id::SteamId;
+use crate::steam_requester::{combine_account_info, score_account_overlap, AccountInfo};
+use crate::util::{maps_intersection, time_fn, time_fn_async};
 
-type HeapItem = (String, String, f32);
-type Heap = MaxHeap<HeapItem, String>;
+/// Accounts listed in reply to `Message::Status`.
+const STATUS_CANDIDATES: usize = 5;
+
+/// Name, SteamID, score and number of hops from the source.
+type HeapItem = (String, SteamId, f32, usize);
+type Heap = MaxHeap<HeapItem, SteamId>;
+
+/// Accounts that could not be fetched even after retrying, with the reason they failed.
+pub type Failures = HashMap<SteamId, String>;
+
+struct Expansion {
+    person: SteamId,
+    friends: Vec<SteamId>,
+    queue: Heap,
+    preds: HashMap<SteamId, SteamId>,
+    failures: Failures,
+}
+
+/// Greedy expansions still running, with the queue entries they were started from.
+struct InFlight {
+    tasks: JoinSet<Result<Expansion, FetchError>>,
+    items: HashMap<SteamId, HeapItem>,
+}
+
+/// Expanded account, its hop count and its friends or the reason they could not be fetched.
+type BfsExpansion = (SteamId, usize, Result<Vec<(String, SteamId)>, String>);
+type FriendsReply = (SteamId, usize, Result<FriendList, String>);
+
+/// One end of a bidirectional search, grown a level at a time in batches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Side {
+    root: SteamId,
+    /// Accounts `depth` hops from the root, the level being expanded.
+    frontier: Vec<SteamId>,
+    /// Accounts of `frontier` expanded so far.
+    #[serde(default)]
+    expanded: usize,
+    /// Accounts reached from `frontier`, which become the next level once it is done.
+    #[serde(default)]
+    next: Vec<SteamId>,
+    depth: usize,
+    preds: HashMap<SteamId, SteamId>,
+    hops: HashMap<SteamId, usize>,
+}
+
+impl Side {
+    fn new(root: SteamId) -> Self {
+        Side {
+            root: root,
+            frontier: vec![root],
+            expanded: 0,
+            next: Vec::new(),
+            depth: 0,
+            preds: HashMap::new(),
+            hops: HashMap::from([(root, 0)]),
+        }
+    }
+
+    fn is_mid_level(&self) -> bool {
+        self.expanded > 0
+    }
+
+    fn waiting(&self) -> usize {
+        self.frontier.len() - self.expanded + self.next.len()
+    }
+}
+
+/// Name, (SteamID, hops from the source) and negated A* cost, so the cheapest entry is on top.
+/// Each account is queued once, and moved up when a shorter route to it is found.
+type AStarItem = (String, (SteamId, usize), f32);
+type AStarHeap = MaxHeap<AStarItem, SteamId>;
+
+/// An entry of either heap as written to a checkpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Queued {
+    name: String,
+    steam_id: SteamId,
+    hops: usize,
+    #[serde(with = "nan_as_null")]
+    priority: f32,
+}
+
+impl Queued {
+    fn from_greedy((name, steam_id, score, hops): HeapItem) -> Self {
+        Queued {
+            name: name,
+            steam_id: steam_id,
+            hops: hops,
+            priority: score,
+        }
+    }
+
+    fn into_greedy(self) -> HeapItem {
+        (self.name, self.steam_id, self.priority, self.hops)
+    }
+
+    fn from_astar((name, (steam_id, hops), priority): AStarItem) -> Self {
+        Queued {
+            name: name,
+            steam_id: steam_id,
+            hops: hops,
+            priority: priority,
+        }
+    }
+
+    fn into_astar(self) -> AStarItem {
+        (self.name, (self.steam_id, self.hops), self.priority)
+    }
+}
+
+/// What each strategy keeps besides the shared `Progress`, as saved in a checkpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SearchState {
+    Greedy {
+        queue: Vec<Queued>,
+        preds: HashMap<SteamId, SteamId>,
+        /// Whether `search.max_frontier` ever dropped accounts from the queue.
+        #[serde(default)]
+        truncated: bool,
+    },
+    Bfs {
+        frontier: VecDeque<(SteamId, usize)>,
+        preds: HashMap<SteamId, SteamId>,
+    },
+    Bidirectional {
+        forward: Side,
+        backwards: Vec<Side>,
+    },
+    AStar {
+        queue: Vec<Queued>,
+        preds: HashMap<SteamId, SteamId>,
+        best_hops: HashMap<SteamId, usize>,
+        closed: HashSet<SteamId>,
+        private: HashSet<SteamId>,
+        truncated: bool,
+    },
+}
+
+impl SearchState {
+    fn visited(&self) -> usize {
+        match self {
+            SearchState::Greedy { preds, .. } | SearchState::Bfs { preds, .. } => preds.len() + 1,
+            SearchState::Bidirectional { forward, backwards } => {
+                forward.hops.len() + backwards.iter().map(|side| side.hops.len()).sum::<usize>()
+            }
+            SearchState::AStar { best_hops, .. } => best_hops.len(),
+        }
+    }
+
+    fn waiting(&self) -> usize {
+        match self {
+            SearchState::Greedy { queue, .. } | SearchState::AStar { queue, .. } => queue.len(),
+            SearchState::Bfs { frontier, .. } => frontier.len(),
+            SearchState::Bidirectional { forward, backwards } => {
+                forward.waiting() + backwards.iter().map(Side::waiting).sum::<usize>()
+            }
+        }
+    }
+
+    fn depth(&self) -> usize {
+        match self {
+            SearchState::Greedy { queue, .. } | SearchState::AStar { queue, .. } => {
+                queue.iter().map(|item| item.hops).max().unwrap_or(0)
+            }
+            SearchState::Bfs { frontier, .. } => frontier.back().map_or(0, |(_, hops)| *hops),
+            SearchState::Bidirectional { forward, backwards } => backwards
+                .iter()
+                .chain([forward])
+                .map(|side| side.depth)
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Up to `count` accounts waiting to be expanded, best first, for the strategies that rank
+    /// them.
+    fn best(&self, count: usize) -> Vec<Candidate> {
+        let (queue, is_cost) = match self {
+            SearchState::Greedy { queue, .. } => (queue, false),
+            SearchState::AStar { queue, .. } => (queue, true),
+            _ => return Vec::new(),
+        };
+
+        let mut ranked = queue
+            .iter()
+            .filter(|item| !item.priority.is_nan())
+            .collect::<Vec<&Queued>>();
+
+        ranked.sort_by(|a, b| b.priority.total_cmp(&a.priority));
+
+        ranked
+            .into_iter()
+            .take(count)
+            .map(|item| Candidate {
+                steam_id: item.steam_id,
+                hops: item.hops,
+                value: if is_cost {
+                    -item.priority
+                } else {
+                    item.priority
+                },
+            })
+            .collect()
+    }
+
+    /// Path from the most promising account waiting to be expanded back to the source. Strategies
+    /// that do not rank accounts use the one reached last.
+    fn best_path(&self, source: SteamId) -> Option<Vec<SteamId>> {
+        let (best, preds) = match self {
+            SearchState::Greedy { preds, .. } | SearchState::AStar { preds, .. } => (
+                self.best(1).first().map(|candidate| candidate.steam_id),
+                preds,
+            ),
+            SearchState::Bfs { frontier, preds } => {
+                (frontier.back().map(|(steam_id, _)| *steam_id), preds)
+            }
+            SearchState::Bidirectional { forward, .. } => (
+                forward.next.last().or(forward.frontier.last()).copied(),
+                &forward.preds,
+            ),
+        };
+
+        best.map(|steam_id| walk_preds(preds, steam_id, source))
+    }
+}
+
+enum ScoreOutcome {
+    Scored(String, SteamId, f32),
+    Skipped(SteamId),
+    Failed(SteamId, String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SearchOutcome {
+    /// Accounts from the target back to the source.
+    Found(Vec<SteamId>),
+    /// Every account within `max_depth` hops was explored without reaching the target.
+    NotFound { max_depth: usize },
+    /// The queue ran dry without reaching the target, but accounts dropped to keep it within
+    /// `max_frontier` were never explored and may still lead to it.
+    FrontierExhausted { max_frontier: usize },
+    /// The search was stopped before reaching the target.
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetResult {
+    pub target: SteamId,
+    pub outcome: SearchOutcome,
+    /// Whether no shorter path can exist among the accounts that could be fetched.
+    pub optimal: bool,
+    /// The best `search.paths` paths through the accounts explored, when more than one is asked for.
+    pub alternatives: Vec<RankedPath>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchReport {
+    /// One result per target, in the order the targets were found.
+    pub results: Vec<TargetResult>,
+    pub failures: Failures,
+}
+
+/// Targets still being looked for, the results for the ones already found and what was learned
+/// about the friend graph on the way. Shared by every strategy.
+struct Progress {
+    remaining: HashSet<SteamId>,
+    results: Vec<TargetResult>,
+    graph: FriendGraph,
+    failures: Failures,
+    iterations: usize,
+    /// Iteration the last checkpoint was saved at.
+    last_saved: usize,
+    /// Accounts expanded per iteration, which can be changed while the search runs.
+    batch_size: usize,
+    /// Accounts never to expand.
+    blacklist: HashSet<SteamId>,
+    found_sender: UnboundedSender<TargetResult>,
+}
+
+impl Progress {
+    fn is_target(&self, steam_id: &SteamId) -> bool {
+        self.remaining.contains(steam_id)
+    }
+
+    fn is_done(&self) -> bool {
+        self.remaining.is_empty()
+    }
+}
 
 pub struct Searcher {
-    source: &'static str,
-    dest_account_info: AccountInfo,
-    target_link: &'static str,
+    backend: Arc<dyn SteamBackend>,
+    source: SteamId,
+    targets: Vec<SteamId>,
+    /// Accounts candidates are scored against: one per target, or a single combined profile.
+    profiles: Vec<AccountInfo>,
+    settings: SearchConfig,
+    weights: ScoringWeights,
+    checkpoint: CheckpointConfig,
+    /// Checkpoint the search continues from instead of starting at the source.
+    resumed: Option<Checkpoint>,
+    /// Cancelled to stop the search, dropping any fetches still in flight.
+    cancel: CancellationToken,
 }
 
 impl Searcher {
-    pub async fn new(source: &'static str, target_link: &'static str) -> Self {
-        let account_info = build_account_info(target_link.to_string()).await.unwrap();
+    pub async fn new(
+        backend: Arc<dyn SteamBackend>,
+        settings: SearchConfig,
+        weights: ScoringWeights,
+        checkpoint: CheckpointConfig,
+        source_link: &str,
+        target_links: &[String],
+    ) -> Result<Self, Box<dyn Error>> {
+        let source = backend.resolve(source_link.to_string()).await?;
 
-        Searcher {
+        let mut targets: Vec<SteamId> = Vec::new();
+        let mut target_infos: Vec<AccountInfo> = Vec::new();
+
+        for target_link in target_links {
+            let target = backend.resolve(target_link.clone()).await?;
+
+            if targets.contains(&target) {
+                continue;
+            }
+
+            targets.push(target);
+            target_infos.push(backend.account_info(target).await?);
+        }
+
+        if targets.is_empty() {
+            return Err(SteamError::boxed_new("At least one target is needed"));
+        }
+
+        let profiles = match settings.target_scoring {
+            TargetScoring::Combined => vec![combine_account_info(&target_infos)],
+            TargetScoring::PerTarget => target_infos,
+        };
+
+        Ok(Searcher {
+            backend: backend,
             source: source,
-            dest_account_info: account_info,
-            target_link: target_link,
+            targets: targets,
+            profiles: profiles,
+            settings: settings,
+            weights: weights,
+            checkpoint: checkpoint,
+            resumed: None,
+            cancel: CancellationToken::new(),
+        })
+    }
+
+    /// Continues the search saved in `saved`, with the settings it was started with.
+    pub fn resume(
+        backend: Arc<dyn SteamBackend>,
+        saved: Checkpoint,
+        checkpoint: CheckpointConfig,
+    ) -> Self {
+        Searcher {
+            backend: backend,
+            source: saved.source,
+            targets: saved.targets.clone(),
+            profiles: saved.profiles.clone(),
+            settings: saved.settings,
+            weights: saved.weights,
+            checkpoint: checkpoint,
+            resumed: Some(saved),
+            cancel: CancellationToken::new(),
         }
     }
 
-    fn cmp(item_a: &HeapItem, item_b: &HeapItem) -> Order {
-        let score1 = (*item_a).2;
-        let score2 = (*item_b).2;
+    pub fn settings(&self) -> &SearchConfig {
+        &self.settings
+    }
 
-        if score1 > score2 {
-            Order::Greater
-        } else if score1 == score2 {
-            Order::Equal
-        } else {
-            Order::Smaller
+    /// Token that stops the search when cancelled. The search returns what it has found so far
+    /// and saves a checkpoint to resume from.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Runs `future` to completion, unless the search is cancelled first. Dropping the future
+    /// drops its `JoinSet`, which aborts the fetches it was waiting on.
+    async fn unless_cancelled<T>(&self, future: impl Future<Output = T>) -> Option<T> {
+        tokio::select! {
+            output = future => Some(output),
+            _ = self.cancel.cancelled() => None,
         }
     }
 
-    fn key(item: &HeapItem) -> &String {
+    /// Higher scores first, and accounts that could not be scored last.
+    fn cmp(item_a: &HeapItem, item_b: &HeapItem) -> Ordering {
+        OrderedF32(item_a.2).cmp(&OrderedF32(item_b.2))
+    }
+
+    fn key(item: &HeapItem) -> &SteamId {
         &item.1
     }
 
+    /// Scores one account on its full profile, or on its summary alone.
     async fn score_friend(
+        backend: &dyn SteamBackend,
         name: String,
-        link: String,
-        dst_account_info: &AccountInfo,
-    ) -> Option<(String, String, f32)> {
-        let next_account = match build_account_info(link.clone()).await {
+        steam_id: SteamId,
+        profiles: &[AccountInfo],
+        weights: &ScoringWeights,
+        full: bool,
+    ) -> Result<ScoreOutcome, FetchError> {
+        let lookup = if full {
+            backend.account_info(steam_id)
+        } else {
+            backend.account_summary(steam_id)
+        };
+
+        let next_account = match lookup.await {
             Ok(val) => val,
-            Err(_) => return None,
+            Err(err) => return Ok(ScoreOutcome::Failed(steam_id, failure_reason(err)?)),
         };
 
         if next_account.private {
-            return None;
+            return Ok(ScoreOutcome::Skipped(steam_id));
+        }
+
+        // Scored against whichever target the account is most similar to.
+        let score = profiles
+            .iter()
+            .map(|profile| score_account_overlap(profile, &next_account, weights))
+            .fold(f32::NAN, f32::max);
+
+        Ok(ScoreOutcome::Scored(name, steam_id, score))
+    }
+
+    async fn score_each(
+        backend: &Arc<dyn SteamBackend>,
+        accounts: Vec<(String, SteamId)>,
+        profiles: &Arc<Vec<AccountInfo>>,
+        weights: ScoringWeights,
+        full: bool,
+    ) -> Result<Vec<ScoreOutcome>, FetchError> {
+        let mut score_tasks: JoinSet<Result<ScoreOutcome, FetchError>> = JoinSet::new();
+
+        for (name, steam_id) in accounts {
+            let backend_ref = Arc::clone(backend);
+            let account_ref = Arc::clone(profiles);
+
+            score_tasks.spawn(async move {
+                Self::score_friend(
+                    backend_ref.as_ref(),
+                    name,
+                    steam_id,
+                    &account_ref,
+                    &weights,
+                    full,
+                )
+                .await
+            });
+        }
+
+        score_tasks.join_all().await.into_iter().collect()
+    }
+
+    /// Scores every account in `accounts`. Backends with cheap summaries score all of them on
+    /// their summaries first, then only the best `full_profiles` again on their full profiles.
+    async fn score_friends(
+        backend: &Arc<dyn SteamBackend>,
+        accounts: Vec<(String, SteamId)>,
+        profiles: &Arc<Vec<AccountInfo>>,
+        weights: ScoringWeights,
+        full_profiles: usize,
+    ) -> Result<Vec<ScoreOutcome>, FetchError> {
+        if !backend.has_summaries() {
+            return Self::score_each(backend, accounts, profiles, weights, true).await;
         }
 
-        let score = score_account_overlap(&dst_account_info, &next_account);
+        let mut outcomes = Self::score_each(backend, accounts, profiles, weights, false).await?;
 
-        Some((name, link, score))
+        // Best summaries last, so the shortlist comes off the end.
+        outcomes.sort_by_key(|outcome| match outcome {
+            ScoreOutcome::Scored(_, _, score) => Some(OrderedF32(*score)),
+            _ => None,
+        });
+
+        let scored = outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome, ScoreOutcome::Scored(..)))
+            .count();
+
+        let shortlist = outcomes
+            .split_off(outcomes.len() - scored.min(full_profiles))
+            .into_iter()
+            .filter_map(|outcome| match outcome {
+                ScoreOutcome::Scored(name, steam_id, _) => Some((name, steam_id)),
+                _ => None,
+            })
+            .collect();
+
+        outcomes.extend(Self::score_each(backend, shortlist, profiles, weights, true).await?);
+
+        Ok(outcomes)
     }
 
     async fn search_node_with_score(
-        preds: &HashMap<String, String>,
-        person: String,
-        person_link: String,
-        _max_depth: usize,
-        dst_account_info: Arc<AccountInfo>,
-    ) -> (Heap, HashMap<String, String>) {
-        let mut new_queue = MaxHeap::new(Self::cmp, Self::key);
-        let mut new_preds: HashMap<String, String> = HashMap::new();
-
-        let f_names_and_links = match get_friends(person_link.clone()).await {
+        backend: Arc<dyn SteamBackend>,
+        person_id: SteamId,
+        depth: usize,
+        known: Arc<RwLock<HashSet<SteamId>>>,
+        profiles: Arc<Vec<AccountInfo>>,
+        weights: ScoringWeights,
+        full_profiles: usize,
+    ) -> Result<Expansion, FetchError> {
+        let mut expansion = Expansion {
+            person: person_id,
+            friends: Vec::new(),
+            queue: MaxHeap::new(Self::cmp, Self::key),
+            preds: HashMap::new(),
+            failures: HashMap::new(),
+        };
+
+        let friend_list = match backend.friends(person_id).await {
             Ok(val) => val,
-            Err(_) => return (new_queue, new_preds),
+            Err(err) => {
+                let reason = failure_reason(err)?;
+                expansion.failures.insert(person_id, reason);
+                return Ok(expansion);
+            }
         };
 
-        let mut score_friends_tasks: JoinSet<Option<(String, String, f32)>> = JoinSet::new();
+        if let Some(reason) = friend_list.failure() {
+            expansion.failures.insert(person_id, reason);
+        }
 
-        for (name, link) in f_names_and_links {
-            if let Some(_) = preds.get(&link.clone()) {
-                //println!("Skipping: {}", name);
-                continue;
-            }
+        let f_names_and_ids = friend_list.friends;
 
-            let account_ref = Arc::clone(&dst_account_info);
+        expansion.friends = f_names_and_ids.iter().map(|(_, id)| *id).collect();
 
-            score_friends_tasks
-                .spawn(async move { Self::score_friend(name, link, &account_ref).await });
-        }
+        // Friends already queued keep their first predecessor, so scoring them again is wasted.
+        let unknown = {
+            let known = known.read().unwrap();
 
-        let results = score_friends_tasks.join_all().await;
+            f_names_and_ids
+                .into_iter()
+                .filter(|(_, steam_id)| !known.contains(steam_id))
+                .collect()
+        };
 
-        results
-            .into_iter()
-            .filter_map(|x| x)
-            .for_each(|(name, link, score)| {
-                new_queue.insert((name.clone(), link.clone(), score));
-                new_preds.insert(link.clone(), person_link.clone());
-            });
+        let results =
+            Self::score_friends(&backend, unknown, &profiles, weights, full_profiles).await?;
+
+        for result in results {
+            match result {
+                ScoreOutcome::Scored(name, steam_id, score) => {
+                    expansion.queue.insert((name, steam_id, score, depth + 1));
+                    expansion.preds.insert(steam_id, person_id);
+                }
+                ScoreOutcome::Failed(steam_id, reason) => {
+                    expansion.failures.insert(steam_id, reason);
+                }
+                ScoreOutcome::Skipped(_) => {}
+            }
+        }
 
-        (new_queue, new_preds)
+        Ok(expansion)
     }
 
-    fn collect_batch(
+    /// Starts expanding the best accounts in the queue until `batch_size` are in flight,
+    /// recording any targets among them. Accounts already `max_depth` hops from the source are
+    /// dropped instead of expanded.
+    fn fill_expansions(
         &self,
-        queue: &Heap,
-        preds: &HashMap<String, String>,
-        batch_size: usize,
-        max_depth: usize,
-    ) -> (JoinSet<(Heap, HashMap<String, String>)>, Heap) {
-        let mut task_set = JoinSet::new();
-        let shared_path = Arc::new(preds.clone());
-        let shared_account_info = Arc::new(self.dest_account_info.clone());
+        queue: &mut Heap,
+        preds: &HashMap<SteamId, SteamId>,
+        known: &Arc<RwLock<HashSet<SteamId>>>,
+        profiles: &Arc<Vec<AccountInfo>>,
+        in_flight: &mut InFlight,
+        progress: &mut Progress,
+    ) {
+        while in_flight.items.len() < progress.batch_size {
+            let Some(item) = queue.pop() else {
+                return;
+            };
+
+            let (person, steam_id, score, depth) = item.clone();
+
+            // Friends with a predecessor are never queued again, but the source starts without
+            // one, so the first friend to list it queues it a second time.
+            if progress.blacklist.contains(&steam_id) || (steam_id == self.source && depth > 0) {
+                continue;
+            }
 
-        let mut new_queue = queue.clone();
+            if progress.is_target(&steam_id) {
+                let path = walk_preds(preds, steam_id, self.source);
+                self.record_found(progress, steam_id, path, false);
+
+                if progress.is_done() {
+                    return;
+                }
+            }
 
-        for (person, friends_link, score) in new_queue.pop_many(batch_size) {
-            if friends_link == self.target_link.to_string() {
-                println!("Target acquired...");
-                return (JoinSet::new(), new_queue);
+            if depth >= self.settings.max_depth {
+                continue;
             }
 
-            println!("Examining: {}, {} : {}", person, friends_link, score);
+            println!(
+                "Examining: {}, {} : {} ({} hops)",
+                person, steam_id, score, depth
+            );
 
-            let person_owned = person.clone();
-            let link_owned = friends_link.clone();
-            let path_ref = Arc::clone(&shared_path);
-            let account_ref = Arc::clone(&shared_account_info);
+            let known_ref = Arc::clone(known);
+            let account_ref = Arc::clone(profiles);
+            let backend_ref = Arc::clone(&self.backend);
+            let weights = self.weights;
+            let full_profiles = self.settings.full_profiles;
 
-            task_set.spawn(async move {
+            in_flight.tasks.spawn(async move {
                 Self::search_node_with_score(
-                    &path_ref,
-                    person_owned,
-                    link_owned,
-                    max_depth,
+                    backend_ref,
+                    steam_id,
+                    depth,
+                    known_ref,
                     account_ref,
+                    weights,
+                    full_profiles,
                 )
                 .await
             });
+
+            in_flight.items.insert(steam_id, item);
+        }
+    }
+
+    /// Adds the friends `expansion` scored to the queue, except those another expansion reached
+    /// while it ran, and marks them `known` so later expansions skip them.
+    fn merge_expansion(
+        mut expansion: Expansion,
+        queue: &mut Heap,
+        preds: &mut HashMap<SteamId, SteamId>,
+        known: &RwLock<HashSet<SteamId>>,
+        progress: &mut Progress,
+    ) {
+        progress.failures.extend(expansion.failures);
+        progress
+            .graph
+            .add_friends(expansion.person, expansion.friends);
+
+        for (_, steam_id, score, _) in expansion.queue.unsorted_iter() {
+            progress.graph.set_score(steam_id, score);
+        }
+
+        let mut known = known.write().unwrap();
+
+        // Keeping the first predecessor found keeps the path in line with the hop count of the
+        // queued entry, which also keeps the first insert.
+        for (steam_id, pred) in expansion.preds {
+            match preds.entry(steam_id) {
+                Entry::Occupied(_) => {
+                    expansion.queue.remove(&steam_id);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(pred);
+                    known.insert(steam_id);
+                }
+            }
         }
 
-        (task_set, new_queue)
+        queue.append(&mut expansion.queue);
     }
 
-    pub async fn start_search(
+    /// Greedy state to save, with the accounts still being expanded put back in the queue.
+    fn greedy_state(
+        queue: &Heap,
+        in_flight: &InFlight,
+        preds: &HashMap<SteamId, SteamId>,
+        truncated: bool,
+    ) -> SearchState {
+        SearchState::Greedy {
+            queue: queue
+                .unsorted_iter()
+                .chain(in_flight.items.values().cloned())
+                .map(Queued::from_greedy)
+                .collect(),
+            preds: preds.clone(),
+            truncated: truncated,
+        }
+    }
+
+    /// Fetches the friends of every `(account, hops)` pair at once. Failed accounts come back
+    /// with the reason instead of their friends, and friends that could not be resolved are
+    /// recorded in `failures` under the account that listed them.
+    async fn fetch_friends_batch(
         &self,
-        max_depth: usize,
-        batch_size: usize,
-        mut msg_reciever: Receiver<Message>,
-    ) -> Result<Vec<String>, Box<dyn Error>> {
-        let mut queue: Heap = MaxHeap::new(Self::cmp, Self::key);
+        batch: Vec<(SteamId, usize)>,
+        failures: &mut Failures,
+    ) -> Result<Vec<BfsExpansion>, FetchError> {
+        let mut current_run: JoinSet<Result<FriendsReply, FetchError>> = JoinSet::new();
+
+        for (steam_id, depth) in batch {
+            println!("Examining: {} ({} hops)", steam_id, depth);
+
+            let backend_ref = Arc::clone(&self.backend);
+
+            current_run.spawn(async move {
+                match backend_ref.friends(steam_id).await {
+                    Ok(friends) => Ok((steam_id, depth, Ok(friends))),
+                    Err(err) => Ok((steam_id, depth, Err(failure_reason(err)?))),
+                }
+            });
+        }
+
+        let results = time_fn_async(
+            || async {
+                current_run
+                    .join_all()
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<_>, FetchError>>()
+            },
+            "Running...",
+        )
+        .await?;
+
+        Ok(results
+            .into_iter()
+            .map(|(steam_id, depth, friend_list)| {
+                let friends = friend_list.map(|list| {
+                    if let Some(reason) = list.failure() {
+                        failures.insert(steam_id, reason);
+                    }
+
+                    list.friends
+                });
+
+                (steam_id, depth, friends)
+            })
+            .collect())
+    }
+
+    /// Reports `path` to `target` straight away and stops looking for that target.
+    fn record_found(
+        &self,
+        progress: &mut Progress,
+        target: SteamId,
+        path: Vec<SteamId>,
+        optimal: bool,
+    ) {
+        println!("Target acquired: {}", target);
+
+        let alternatives = if self.settings.paths > 1 {
+            time_fn(
+                || self.alternative_paths(&progress.graph, target),
+                "Finding Alternatives",
+            )
+        } else {
+            Vec::new()
+        };
+
+        let result = TargetResult {
+            target: target,
+            outcome: SearchOutcome::Found(path),
+            optimal: optimal,
+            alternatives: alternatives,
+        };
+
+        // The receiver only prints results early, so the report is still complete without it.
+        let _ = progress.found_sender.send(result.clone());
+
+        progress.remaining.remove(&target);
+        progress.results.push(result);
+    }
+
+    /// Ends the search, reporting every target not found yet as out of reach.
+    fn finish(&self, progress: Progress, optimal: bool) -> SearchReport {
+        let max_depth = self.settings.max_depth;
+
+        self.report(progress, SearchOutcome::NotFound { max_depth }, optimal)
+    }
+
+    /// Ends a search whose queue ran dry. If `search.max_frontier` dropped accounts on the way,
+    /// the targets are reported as cut off by it rather than out of reach.
+    fn finish_frontier(&self, progress: Progress, truncated: bool, optimal: bool) -> SearchReport {
+        if !truncated {
+            return self.finish(progress, optimal);
+        }
+
+        let max_frontier = self.settings.max_frontier;
+
+        self.report(
+            progress,
+            SearchOutcome::FrontierExhausted { max_frontier },
+            false,
+        )
+    }
 
-        let mut preds: HashMap<String, String> = HashMap::new();
+    /// Ends a cancelled search, saving `state` so it can be resumed. Targets not found yet are
+    /// reported as cancelled.
+    fn stop(&self, mut progress: Progress, state: SearchState) -> SearchReport {
+        println!("Search cancelled");
 
-        queue.insert((String::from("START"), self.source.to_string(), 0_f32));
+        match self.save_checkpoint(&mut progress, state) {
+            Ok(()) => println!("Checkpoint saved to {}", self.checkpoint.path.display()),
+            Err(err) => println!("{}", err),
+        }
+
+        self.report(progress, SearchOutcome::Cancelled, false)
+    }
+
+    fn report(&self, progress: Progress, unfound: SearchOutcome, optimal: bool) -> SearchReport {
+        let mut results = progress.results;
+
+        for target in self.targets.iter() {
+            if progress.remaining.contains(target) {
+                results.push(TargetResult {
+                    target: *target,
+                    outcome: unfound.clone(),
+                    optimal: optimal,
+                    alternatives: Vec::new(),
+                });
+            }
+        }
+
+        SearchReport {
+            results: results,
+            failures: progress.failures,
+        }
+    }
 
-        let mut state = Message::None;
+    /// Best paths from `target` back to the source through the friendships seen so far.
+    fn alternative_paths(&self, graph: &FriendGraph, target: SteamId) -> Vec<RankedPath> {
+        let (paths, max_hops) = (self.settings.paths, self.settings.max_depth);
+
+        if self.settings.node_disjoint {
+            graph.disjoint_paths(target, self.source, paths, max_hops)
+        } else {
+            graph.k_shortest_paths(target, self.source, paths, max_hops)
+        }
+    }
 
+    /// Answers every waiting runtime message, then saves a checkpoint of `snapshot` if one is
+    /// due. While paused this waits for the next message instead. Returns false once the search
+    /// has been cancelled.
+    async fn next_iteration(
+        &self,
+        msg_reciever: &mut Receiver<Request>,
+        paused: &mut bool,
+        progress: &mut Progress,
+        snapshot: &impl Fn() -> SearchState,
+    ) -> bool {
         loop {
-            state = match msg_reciever.try_recv() {
-                Ok(message) => message,
-                _ => state,
+            let request = if *paused {
+                tokio::select! {
+                    request = msg_reciever.recv() => request,
+                    _ = self.cancel.cancelled() => return false,
+                }
+            } else {
+                msg_reciever.try_recv().ok()
             };
 
-            match state {
-                Message::Pause => {
-                    print!("Paused.... \r");
-                    continue;
-                }
-                Message::Quit => {
-                    return Err(SteamError::boxed_new("Quit Initiated"));
-                }
-                _ => {}
-            }
+            let Some(Request { message, reply }) = request else {
+                // Once the reader is gone nothing could continue a paused search, so carry on.
+                *paused = false;
+                break;
+            };
 
-            let (current_run, new_queue) = time_fn(
-                || self.collect_batch(&queue, &preds, batch_size, max_depth),
-                "Collecting Runs",
-            );
+            // The reader may have stopped waiting, which only loses the reply.
+            let _ = reply.send(self.apply_message(message, paused, progress, snapshot));
+        }
+
+        if self.cancel.is_cancelled() {
+            return false;
+        }
 
-            if current_run.is_empty() {
-                let mut path = vec![self.target_link.to_string()];
-                let mut cur = self.target_link.to_string();
+        let every = self.checkpoint.every;
 
-                while let Some(pred) = preds.get(&cur) {
-                    if pred.clone() == String::new() {
-                        break;
-                    }
+        if every > 0 && progress.iterations >= progress.last_saved + every {
+            match self.save_checkpoint(progress, snapshot()) {
+                Ok(()) => println!("Checkpoint saved to {}", self.checkpoint.path.display()),
+                Err(err) => println!("{}", err),
+            }
+        }
 
-                    path.push(pred.clone());
-                    cur = pred.clone();
+        progress.iterations += 1;
 
-                    if cur == self.source.to_string() {
-                        break;
-                    }
-                }
+        true
+    }
+
+    fn apply_message(
+        &self,
+        message: Message,
+        paused: &mut bool,
+        progress: &mut Progress,
+        snapshot: &impl Fn() -> SearchState,
+    ) -> Response {
+        let path = self.checkpoint.path.display();
 
-                return Ok(path);
+        match message {
+            Message::Pause => {
+                *paused = true;
+                Response::Done(String::from("Paused"))
+            }
+            Message::Continue => {
+                *paused = false;
+                Response::Done(String::from("Continuing"))
             }
+            Message::Status => {
+                let state = snapshot();
 
-            let (queues, paths) = time_fn_async(
-                || async { unzip_tuple_lists(current_run.join_all().await) },
-                "Running...",
-            )
-            .await;
+                Response::Status(Status {
+                    strategy: self.settings.strategy,
+                    paused: *paused,
+                    iterations: progress.iterations,
+                    batch_size: progress.batch_size,
+                    visited: state.visited(),
+                    frontier: state.waiting(),
+                    depth: state.depth(),
+                    found: progress.results.len(),
+                    remaining: progress.remaining.len(),
+                    failures: progress.failures.len(),
+                    best: state.best(STATUS_CANDIDATES),
+                })
+            }
+            Message::BatchSize(0) => {
+                Response::Rejected(String::from("The batch size must be at least 1"))
+            }
+            Message::BatchSize(batch_size) => {
+                progress.batch_size = batch_size;
+                Response::Done(format!("Batch size set to {}", batch_size))
+            }
+            Message::BestPath => Response::Path(snapshot().best_path(self.source)),
+            Message::Checkpoint => match self.save_checkpoint(progress, snapshot()) {
+                Ok(()) => Response::Done(format!("Checkpoint saved to {}", path)),
+                Err(err) => Response::Rejected(err.to_string()),
+            },
+            Message::Blacklist(steam_id) => {
+                if steam_id == self.source || self.targets.contains(&steam_id) {
+                    Response::Rejected(format!("{} is the source or a target", steam_id))
+                } else {
+                    progress.blacklist.insert(steam_id);
+                    Response::Done(format!("{} will not be expanded", steam_id))
+                }
+            }
+        }
+    }
 
-            queue = new_queue;
+    fn save_checkpoint(
+        &self,
+        progress: &mut Progress,
+        state: SearchState,
+    ) -> Result<(), CheckpointError> {
+        let checkpoint = Checkpoint {
+            version: CHECKPOINT_VERSION,
+            source: self.source,
+            targets: self.targets.clone(),
+            profiles: self.profiles.clone(),
+            settings: SearchConfig {
+                batch_size: progress.batch_size,
+                ..self.settings
+            },
+            weights: self.weights,
+            results: progress.results.clone(),
+            graph: progress.graph.clone(),
+            failures: progress.failures.clone(),
+            blacklist: progress.blacklist.clone(),
+            iterations: progress.iterations,
+            state: state,
+        };
 
-            queue = time_fn(
-                || {
-                    queues
-                        .iter()
-                        .fold(queue.clone(), |acc, next| acc.combine_with(next))
-                },
-                "Combining Heaps",
-            );
+        time_fn(
+            || checkpoint.save(&self.checkpoint.path),
+            "Saving Checkpoint",
+        )?;
 
-            println!("Heap Size: {}", queue.len());
+        progress.last_saved = progress.iterations;
 
-            queue.truncate(100000);
+        Ok(())
+    }
 
-            preds = time_fn(
-                || {
-                    paths
-                        .clone()
-                        .into_iter()
-                        .fold(preds.clone(), |mut acc, next| {
-                            acc.extend(next);
-                            acc
-                        })
-                },
-                "Combining Preds",
-            );
+    /// Searches until every target is found or cannot be reached within the limits. Each path is
+    /// also sent on `found_sender` as soon as it is found.
+    pub async fn start_search(
+        &self,
+        msg_reciever: Receiver<Request>,
+        found_sender: UnboundedSender<TargetResult>,
+    ) -> Result<SearchReport, Box<dyn Error>> {
+        let (progress, resumed) = match &self.resumed {
+            Some(saved) => {
+                // Paths found before the checkpoint are reported again alongside the new ones.
+                for result in saved.results.iter() {
+                    let _ = found_sender.send(result.clone());
+                }
 
-            //println!("Preds: {:?}", preds);
+                let progress = Progress {
+                    remaining: self
+                        .targets
+                        .iter()
+                        .filter(|target| saved.results.iter().all(|found| found.target != **target))
+                        .copied()
+                        .collect(),
+                    results: saved.results.clone(),
+                    graph: saved.graph.clone(),
+                    failures: saved.failures.clone(),
+                    iterations: saved.iterations,
+                    last_saved: saved.iterations,
+                    batch_size: self.settings.batch_size,
+                    blacklist: saved.blacklist.clone(),
+                    found_sender: found_sender,
+                };
 
-            /*println!(
-                "End pred: {:?}",
-                preds.get(&String::from(
-                    "Dr.Disrepect,https://steamcommunity.com/profiles/76561198043820228"
-                ))
-            );*/
+                (progress, Some(saved.state.clone()))
+            }
+            None => {
+                let progress = Progress {
+                    remaining: self.targets.iter().copied().collect(),
+                    results: Vec::new(),
+                    graph: FriendGraph::new(),
+                    failures: HashMap::new(),
+                    iterations: 0,
+                    last_saved: 0,
+                    batch_size: self.settings.batch_size,
+                    blacklist: HashSet::new(),
+                    found_sender: found_sender,
+                };
+
+                (progress, None)
+            }
+        };
+
+        match self.settings.strategy {
+            Strategy::Greedy => self.greedy_search(msg_reciever, progress, resumed).await,
+            Strategy::AStar => self.astar_search(msg_reciever, progress, resumed).await,
+            Strategy::Bfs => self.bfs_search(msg_reciever, progress, resumed).await,
+            Strategy::Bidirectional => {
+                self.bidirectional_search(msg_reciever, progress, resumed)
+                    .await
+            }
+        }
+    }
+
+    /// Best-first search on `score_account_overlap`. Usually fast, but the path it finds is not
+    /// guaranteed to be the shortest. Up to `batch_size` accounts are expanded at once, and an
+    /// iteration ends once that many have finished.
+    async fn greedy_search(
+        &self,
+        mut msg_reciever: Receiver<Request>,
+        mut progress: Progress,
+        resumed: Option<SearchState>,
+    ) -> Result<SearchReport, Box<dyn Error>> {
+        let mut queue: Heap = MaxHeap::new(Self::cmp, Self::key);
+
+        let (mut preds, mut truncated) = match resumed {
+            Some(SearchState::Greedy {
+                queue: saved_queue,
+                preds,
+                truncated,
+            }) => {
+                for item in saved_queue {
+                    queue.insert(item.into_greedy());
+                }
+
+                (preds, truncated)
+            }
+            _ => {
+                queue.insert((String::from("START"), self.source, 0_f32, 0));
+
+                (HashMap::new(), false)
+            }
+        };
+
+        // Accounts with a predecessor, shared with the expansions so they only score new friends.
+        let known = preds
+            .keys()
+            .copied()
+            .chain([self.source])
+            .collect::<HashSet<SteamId>>();
+        let known = Arc::new(RwLock::new(known));
+
+        let profiles = Arc::new(self.profiles.clone());
+
+        let mut in_flight = InFlight {
+            tasks: JoinSet::new(),
+            items: HashMap::new(),
+        };
+
+        let mut paused = false;
+
+        loop {
+            let snapshot = || Self::greedy_state(&queue, &in_flight, &preds, truncated);
+
+            if !self
+                .next_iteration(&mut msg_reciever, &mut paused, &mut progress, &snapshot)
+                .await
+            {
+                return Ok(self.stop(progress, snapshot()));
+            }
+
+            let mut expanded = 0;
+
+            // A finished expansion feeds the queue straight away and frees its slot for the next
+            // best account, so one huge friend list only holds up its own slot.
+            while expanded < progress.batch_size {
+                self.fill_expansions(
+                    &mut queue,
+                    &preds,
+                    &known,
+                    &profiles,
+                    &mut in_flight,
+                    &mut progress,
+                );
+
+                if progress.is_done() {
+                    return Ok(self.finish(progress, false));
+                }
+
+                let Some(joined) = self.unless_cancelled(in_flight.tasks.join_next()).await else {
+                    let state = Self::greedy_state(&queue, &in_flight, &preds, truncated);
+
+                    return Ok(self.stop(progress, state));
+                };
+
+                // No task is left once the queue runs dry.
+                let Some(joined) = joined else {
+                    return Ok(self.finish_frontier(progress, truncated, false));
+                };
+
+                let expansion = match joined {
+                    Ok(expansion) => expansion?,
+                    Err(err) => std::panic::resume_unwind(err.into_panic()),
+                };
+
+                in_flight.items.remove(&expansion.person);
+
+                Self::merge_expansion(expansion, &mut queue, &mut preds, &known, &mut progress);
+
+                expanded += 1;
+            }
+
+            println!(
+                "Heap Size: {}, Expanding: {}, Failed accounts: {}",
+                queue.len(),
+                in_flight.items.len(),
+                progress.failures.len()
+            );
+
+            if queue.len() > self.settings.max_frontier {
+                queue.truncate(self.settings.max_frontier);
+                truncated = true;
+            }
+
+            //println!("Preds: {:?}", preds);
+
+            /*println!(
+                "End pred: {:?}",
+                preds.get(&String::from(
+                    "Dr.Disrepect,https://steamcommunity.com/profiles/76561198043820228"
+                ))
+            );*/
 
             println!("Complete iteration");
         }
     }
+
+    /// Breadth-first search over friend lists. Accounts are expanded in order of hop count, so
+    /// the first path found to each target has the fewest hops possible.
+    async fn bfs_search(
+        &self,
+        mut msg_reciever: Receiver<Request>,
+        mut progress: Progress,
+        resumed: Option<SearchState>,
+    ) -> Result<SearchReport, Box<dyn Error>> {
+        let (mut frontier, mut preds) = match resumed {
+            Some(SearchState::Bfs { frontier, preds }) => (frontier, preds),
+            _ => (VecDeque::from([(self.source, 0)]), HashMap::new()),
+        };
+
+        let mut paused = false;
+
+        if progress.is_target(&self.source) {
+            self.record_found(&mut progress, self.source, vec![self.source], true);
+        }
+
+        loop {
+            let snapshot = || SearchState::Bfs {
+                frontier: frontier.clone(),
+                preds: preds.clone(),
+            };
+
+            if !self
+                .next_iteration(&mut msg_reciever, &mut paused, &mut progress, &snapshot)
+                .await
+            {
+                return Ok(self.stop(progress, snapshot()));
+            }
+
+            if frontier.is_empty() || progress.is_done() {
+                return Ok(self.finish(progress, true));
+            }
+
+            let batch_size = progress.batch_size.min(frontier.len());
+
+            let batch = frontier
+                .iter()
+                .take(batch_size)
+                .filter(|(steam_id, _)| !progress.blacklist.contains(steam_id))
+                .copied()
+                .collect();
+
+            // The batch stays in the frontier until it is done, so a cancelled one is saved.
+            let Some(results) = self
+                .unless_cancelled(self.fetch_friends_batch(batch, &mut progress.failures))
+                .await
+            else {
+                return Ok(self.stop(progress, snapshot()));
+            };
+
+            let mut results = results?;
+
+            frontier.drain(..batch_size);
+
+            // Tasks finish in any order, but a batch can straddle two levels and the shallower
+            // accounts must claim their friends first for the hop counts to stay minimal.
+            results.sort_by_key(|(_, depth, _)| *depth);
+
+            for (person_id, depth, friends) in results {
+                let friends = match friends {
+                    Ok(friends) => friends,
+                    Err(reason) => {
+                        progress.failures.insert(person_id, reason);
+                        continue;
+                    }
+                };
+
+                progress
+                    .graph
+                    .add_friends(person_id, friends.iter().map(|(_, id)| *id));
+
+                for (_, steam_id) in friends {
+                    if steam_id == self.source
+                        || preds.contains_key(&steam_id)
+                        || progress.blacklist.contains(&steam_id)
+                    {
+                        continue;
+                    }
+
+                    preds.insert(steam_id, person_id);
+
+                    if progress.is_target(&steam_id) {
+                        let path = walk_preds(&preds, steam_id, self.source);
+                        self.record_found(&mut progress, steam_id, path, true);
+                    }
+
+                    if depth + 1 < self.settings.max_depth {
+                        frontier.push_back((steam_id, depth + 1));
+                    }
+                }
+            }
+
+            println!(
+                "Visited: {}, Frontier: {}, Failed accounts: {}",
+                preds.len(),
+                frontier.len(),
+                progress.failures.len()
+            );
+        }
+    }
+
+    /// Breadth-first search from the source and every target at once, always growing the smallest
+    /// frontier. Friendship is mutual, so the halves join into a shortest path where they meet.
+    async fn bidirectional_search(
+        &self,
+        mut msg_reciever: Receiver<Request>,
+        mut progress: Progress,
+        resumed: Option<SearchState>,
+    ) -> Result<SearchReport, Box<dyn Error>> {
+        let (mut forward, mut backwards) = match resumed {
+            Some(SearchState::Bidirectional { forward, backwards }) => (forward, backwards),
+            _ => {
+                let mut backwards: Vec<Side> = Vec::new();
+
+                for target in self.targets.iter() {
+                    if *target == self.source {
+                        self.record_found(&mut progress, self.source, vec![self.source], true);
+                    } else {
+                        backwards.push(Side::new(*target));
+                    }
+                }
+
+                (Side::new(self.source), backwards)
+            }
+        };
+
+        let mut paused = false;
+
+        loop {
+            let snapshot = || SearchState::Bidirectional {
+                forward: forward.clone(),
+                backwards: backwards.clone(),
+            };
+
+            if !self
+                .next_iteration(&mut msg_reciever, &mut paused, &mut progress, &snapshot)
+                .await
+            {
+                return Ok(self.stop(progress, snapshot()));
+            }
+
+            // Targets whose side can no longer meet the source side within the limit stay unfound.
+            backwards.retain(|backward| {
+                let exhausted = forward.frontier.is_empty() && backward.frontier.is_empty();
+
+                !exhausted && forward.depth + backward.depth < self.settings.max_depth
+            });
+
+            if backwards.is_empty() {
+                return Ok(self.finish(progress, true));
+            }
+
+            // A level is finished before another is started, so the first meeting found for each
+            // target is on a shortest path.
+            let expanded = if forward.is_mid_level() {
+                None
+            } else if let Some(index) = backwards.iter().position(Side::is_mid_level) {
+                Some(index)
+            } else {
+                // A side whose frontier ran dry (e.g. a private target) leaves the others to go on
+                // alone.
+                let smallest_backward = (0..backwards.len())
+                    .filter(|index| !backwards[*index].frontier.is_empty())
+                    .min_by_key(|index| backwards[*index].frontier.len());
+
+                match smallest_backward {
+                    Some(index)
+                        if forward.frontier.is_empty()
+                            || forward.frontier.len() > backwards[index].frontier.len() =>
+                    {
+                        Some(index)
+                    }
+                    _ => None,
+                }
+            };
+
+            let side = match expanded {
+                Some(index) => &mut backwards[index],
+                None => &mut forward,
+            };
+
+            // A side is only updated once its batch is done, so a cancelled one is saved.
+            let Some(discovered) = self
+                .unless_cancelled(self.expand_batch(side, &mut progress))
+                .await
+            else {
+                let state = SearchState::Bidirectional {
+                    forward: forward.clone(),
+                    backwards: backwards.clone(),
+                };
+
+                return Ok(self.stop(progress, state));
+            };
+
+            let discovered = discovered?;
+
+            let meetings = match expanded {
+                Some(index) => closest_meeting(&discovered, &forward.hops)
+                    .map(|meeting| (index, meeting))
+                    .into_iter()
+                    .collect::<Vec<(usize, SteamId)>>(),
+                None => backwards
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, backward)| {
+                        closest_meeting(&discovered, &backward.hops).map(|meeting| (index, meeting))
+                    })
+                    .collect(),
+            };
+
+            println!(
+                "Source side: {} accounts, Target sides: {} accounts, Failed accounts: {}",
+                forward.hops.len(),
+                backwards.iter().map(|side| side.hops.len()).sum::<usize>(),
+                progress.failures.len()
+            );
+
+            for (index, meeting) in meetings.iter() {
+                let backward = &backwards[*index];
+
+                println!("Frontiers met at {}", meeting);
+
+                let mut path = walk_preds(&backward.preds, *meeting, backward.root);
+                path.reverse();
+                path.extend(
+                    walk_preds(&forward.preds, *meeting, self.source)
+                        .into_iter()
+                        .skip(1),
+                );
+
+                self.record_found(&mut progress, backward.root, path, true);
+            }
+
+            backwards.retain(|backward| progress.is_target(&backward.root));
+        }
+    }
+
+    /// Expands the next batch of the level `side` is on, returning the newly reached accounts
+    /// with their hop counts. The next level starts once the last batch is done.
+    async fn expand_batch(
+        &self,
+        side: &mut Side,
+        progress: &mut Progress,
+    ) -> Result<HashMap<SteamId, usize>, FetchError> {
+        let depth = side.depth;
+
+        if !side.is_mid_level() {
+            println!(
+                "Expanding side of {}: {} accounts at {} hops",
+                side.root,
+                side.frontier.len(),
+                depth
+            );
+        }
+
+        let end = (side.expanded + progress.batch_size).min(side.frontier.len());
+
+        let batch = side.frontier[side.expanded..end]
+            .iter()
+            .filter(|steam_id| !progress.blacklist.contains(steam_id))
+            .map(|steam_id| (*steam_id, depth))
+            .collect();
+
+        let results = self
+            .fetch_friends_batch(batch, &mut progress.failures)
+            .await?;
+
+        side.expanded = end;
+
+        let mut discovered: HashMap<SteamId, usize> = HashMap::new();
+
+        for (person_id, _, friends) in results {
+            let friends = match friends {
+                Ok(friends) => friends,
+                Err(reason) => {
+                    progress.failures.insert(person_id, reason);
+                    continue;
+                }
+            };
+
+            progress
+                .graph
+                .add_friends(person_id, friends.iter().map(|(_, id)| *id));
+
+            for (_, steam_id) in friends {
+                if side.hops.contains_key(&steam_id) || progress.blacklist.contains(&steam_id) {
+                    continue;
+                }
+
+                side.hops.insert(steam_id, depth + 1);
+                side.preds.insert(steam_id, person_id);
+                side.next.push(steam_id);
+                discovered.insert(steam_id, depth + 1);
+            }
+        }
+
+        if side.expanded == side.frontier.len() {
+            side.frontier = std::mem::take(&mut side.next);
+            side.expanded = 0;
+            side.depth += 1;
+        }
+
+        Ok(discovered)
+    }
+
+    /// Lower bound on the hops left from an account with the given similarity score. Any account
+    /// other than a target is at least one hop away, so keeping this within 0..=1 makes it
+    /// admissible and consistent, while more similar accounts still look closer.
+    fn hop_heuristic(&self, score: f32) -> f32 {
+        let total = self.weights.country
+            + self.weights.recent_games
+            + self.weights.favorite_game
+            + self.weights.friends
+            + self.weights.groups;
+
+        let similarity = (score / total as f32).clamp(0_f32, 1_f32);
+
+        if similarity.is_nan() {
+            1_f32
+        } else {
+            1_f32 - similarity
+        }
+    }
+
+    fn astar_cmp(item_a: &AStarItem, item_b: &AStarItem) -> Ordering {
+        OrderedF32(item_a.2).cmp(&OrderedF32(item_b.2))
+    }
+
+    fn astar_key(item: &AStarItem) -> &SteamId {
+        &item.1 .0
+    }
+
+    /// Queues `item`, or moves its account up if it is already queued at a higher cost.
+    fn queue_astar(queue: &mut AStarHeap, item: AStarItem) {
+        match queue.get(&item.1 .0) {
+            Some(queued) if queued.2 >= item.2 => {}
+            Some(_) => {
+                queue.update_priority(item);
+            }
+            None => queue.insert(item),
+        }
+    }
+
+    /// Scores every account in `steam_ids` against the targets, recording accounts that are
+    /// private or could not be fetched.
+    async fn score_accounts(
+        &self,
+        steam_ids: Vec<SteamId>,
+        progress: &mut Progress,
+        private: &mut HashSet<SteamId>,
+    ) -> Result<(), FetchError> {
+        let accounts = steam_ids
+            .into_iter()
+            .map(|steam_id| (String::new(), steam_id))
+            .collect();
+
+        let results = Self::score_friends(
+            &self.backend,
+            accounts,
+            &Arc::new(self.profiles.clone()),
+            self.weights,
+            self.settings.full_profiles,
+        )
+        .await?;
+
+        for result in results {
+            match result {
+                ScoreOutcome::Scored(_, steam_id, score) => {
+                    progress.graph.set_score(steam_id, score);
+                }
+                ScoreOutcome::Skipped(steam_id) => {
+                    private.insert(steam_id);
+                }
+                ScoreOutcome::Failed(steam_id, reason) => {
+                    progress.failures.insert(steam_id, reason);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A* over hop counts, ordered by `hops + heuristic_weight * hop_heuristic(score)`. With a
+    /// weight of at most 1 the first path to each target is a shortest one; larger weights lean
+    /// towards similar accounts and usually finish sooner, without that guarantee.
+    async fn astar_search(
+        &self,
+        mut msg_reciever: Receiver<Request>,
+        mut progress: Progress,
+        resumed: Option<SearchState>,
+    ) -> Result<SearchReport, Box<dyn Error>> {
+        let mut queue: AStarHeap = MaxHeap::new(Self::astar_cmp, Self::astar_key);
+
+        let (mut preds, mut best_hops, mut closed, mut private, mut truncated) = match resumed {
+            Some(SearchState::AStar {
+                queue: saved_queue,
+                preds,
+                best_hops,
+                closed,
+                private,
+                truncated,
+            }) => {
+                // Older checkpoints can hold an account more than once, even after it was
+                // expanded, so only the cheapest entry of accounts still open is kept.
+                for item in saved_queue {
+                    if !closed.contains(&item.steam_id) {
+                        Self::queue_astar(&mut queue, item.into_astar());
+                    }
+                }
+
+                (preds, best_hops, closed, private, truncated)
+            }
+            _ => {
+                queue.insert((String::from("START"), (self.source, 0), 0_f32));
+
+                (
+                    HashMap::new(),
+                    HashMap::from([(self.source, 0)]),
+                    HashSet::new(),
+                    HashSet::new(),
+                    false,
+                )
+            }
+        };
+
+        let weight = self.settings.heuristic_weight as f32;
+
+        let mut paused = false;
+
+        loop {
+            let snapshot = || SearchState::AStar {
+                queue: queue.unsorted_iter().map(Queued::from_astar).collect(),
+                preds: preds.clone(),
+                best_hops: best_hops.clone(),
+                closed: closed.clone(),
+                private: private.clone(),
+                truncated: truncated,
+            };
+
+            if !self
+                .next_iteration(&mut msg_reciever, &mut paused, &mut progress, &snapshot)
+                .await
+            {
+                return Ok(self.stop(progress, snapshot()));
+            }
+
+            // Accounts blocked while queued are dropped instead of expanded.
+            for steam_id in progress.blacklist.iter() {
+                queue.remove(steam_id);
+            }
+
+            // Accounts within one hop of the cheapest cost are expanded together. None of them
+            // can lie on a shorter route to another, so their hop counts are already final.
+            let mut batch: Vec<AStarItem> = Vec::new();
+            let mut cheapest: Option<f32> = None;
+
+            while batch.len() < progress.batch_size {
+                let Some((_, _, priority)) = queue.peek() else {
+                    break;
+                };
+
+                let cost = -priority;
+
+                if cheapest.is_some_and(|cheapest| cost >= cheapest + 1_f32) {
+                    break;
+                }
+
+                let item = queue.pop().unwrap();
+                let (steam_id, hops) = item.1;
+
+                closed.insert(steam_id);
+
+                if progress.is_target(&steam_id) {
+                    let path = walk_preds(&preds, steam_id, self.source);
+                    let optimal = weight <= 1_f32 && !truncated;
+
+                    self.record_found(&mut progress, steam_id, path, optimal);
+
+                    if progress.is_done() {
+                        return Ok(self.finish(progress, optimal));
+                    }
+                }
+
+                if hops >= self.settings.max_depth {
+                    continue;
+                }
+
+                cheapest.get_or_insert(cost);
+                batch.push(item);
+            }
+
+            if batch.is_empty() {
+                return Ok(self.finish_frontier(progress, truncated, weight <= 1_f32));
+            }
+
+            let expanded = self
+                .unless_cancelled(async {
+                    let steam_ids = batch.iter().map(|item| item.1).collect();
+                    let expansions = self
+                        .fetch_friends_batch(steam_ids, &mut progress.failures)
+                        .await?;
+
+                    let unscored = expansions
+                        .iter()
+                        .filter_map(|(_, _, friends)| friends.as_ref().ok())
+                        .flatten()
+                        .map(|(_, steam_id)| *steam_id)
+                        .filter(|steam_id| {
+                            !progress.is_target(steam_id)
+                                && !closed.contains(steam_id)
+                                && progress.graph.score(steam_id).is_none()
+                                && !private.contains(steam_id)
+                                && !progress.blacklist.contains(steam_id)
+                                && !progress.failures.contains_key(steam_id)
+                        })
+                        .collect::<HashSet<SteamId>>();
+
+                    time_fn_async(
+                        || {
+                            self.score_accounts(
+                                unscored.into_iter().collect(),
+                                &mut progress,
+                                &mut private,
+                            )
+                        },
+                        "Scoring...",
+                    )
+                    .await?;
+
+                    Ok::<_, FetchError>(expansions)
+                })
+                .await;
+
+            let Some(expansions) = expanded else {
+                // The batch goes back into the queue to be expanded on resume.
+                for (_, (steam_id, _), _) in batch.iter() {
+                    closed.remove(steam_id);
+                }
+
+                let state = SearchState::AStar {
+                    queue: queue
+                        .unsorted_iter()
+                        .chain(batch)
+                        .map(Queued::from_astar)
+                        .collect(),
+                    preds: preds,
+                    best_hops: best_hops,
+                    closed: closed,
+                    private: private,
+                    truncated: truncated,
+                };
+
+                return Ok(self.stop(progress, state));
+            };
+
+            let expansions = expansions?;
+
+            for (person_id, hops, friends) in expansions {
+                let friends = match friends {
+                    Ok(friends) => friends,
+                    Err(reason) => {
+                        progress.failures.insert(person_id, reason);
+                        continue;
+                    }
+                };
+
+                progress
+                    .graph
+                    .add_friends(person_id, friends.iter().map(|(_, id)| *id));
+
+                let next_hops = hops + 1;
+
+                for (name, steam_id) in friends {
+                    let is_target = progress.is_target(&steam_id);
+
+                    // Only targets are worth queueing once the depth limit is reached.
+                    if closed.contains(&steam_id)
+                        || progress.blacklist.contains(&steam_id)
+                        || (!is_target && next_hops >= self.settings.max_depth)
+                    {
+                        continue;
+                    }
+
+                    if best_hops
+                        .get(&steam_id)
+                        .is_some_and(|best| *best <= next_hops)
+                    {
+                        continue;
+                    }
+
+                    let heuristic = match progress.graph.score(&steam_id) {
+                        _ if is_target => 0_f32,
+                        Some(score) => self.hop_heuristic(score),
+                        None => continue,
+                    };
+
+                    best_hops.insert(steam_id, next_hops);
+                    preds.insert(steam_id, person_id);
+
+                    let cost = next_hops as f32 + weight * heuristic;
+
+                    Self::queue_astar(&mut queue, (name, (steam_id, next_hops), -cost));
+                }
+            }
+
+            println!(
+                "Heap Size: {}, Closed: {}, Failed accounts: {}",
+                queue.len(),
+                closed.len(),
+                progress.failures.len()
+            );
+
+            if queue.len() > self.settings.max_frontier {
+                queue.truncate(self.settings.max_frontier);
+                truncated = true;
+            }
+        }
+    }
+}
+
+/// Account in both maps with the fewest hops in total, where the two sides of a search meet.
+fn closest_meeting(
+    discovered: &HashMap<SteamId, usize>,
+    other_hops: &HashMap<SteamId, usize>,
+) -> Option<SteamId> {
+    maps_intersection(discovered, other_hops)
+        .min_by_key(|steam_id| discovered[*steam_id] + other_hops[*steam_id])
+        .copied()
+}
+
+/// Follows `preds` from `from` until reaching `stop` or an account with no predecessor.
+fn walk_preds(preds: &HashMap<SteamId, SteamId>, from: SteamId, stop: SteamId) -> Vec<SteamId> {
+    let mut path = vec![from];
+    let mut cur = from;
+
+    while let Some(pred) = preds.get(&cur) {
+        if cur == stop {
+            break;
+        }
+
+        path.push(*pred);
+        cur = *pred;
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::backend::HtmlBackend;
+    use crate::fetcher::{FetchFuture, MemoryFetcher, PageFetcher};
+    use crate::web_api::tests::backend as web_api_backend;
+
+    const SOURCE: u32 = 1;
+    const TARGET: u32 = 9;
+
+    /// Source to target in three hops through 2 and 3, which look nothing like the target, or in
+    /// four through 4, 5 and 6, which look just like it. 7 is a dead end.
+    const EDGES: [(u32, u32); 8] = [
+        (1, 2),
+        (2, 3),
+        (3, 9),
+        (1, 4),
+        (4, 5),
+        (5, 6),
+        (6, 9),
+        (1, 7),
+    ];
+
+    fn id(account_id: u32) -> SteamId {
+        SteamId::from_account_id(account_id)
+    }
+
+    fn profile_page(account_id: u32) -> String {
+        let (country, game) = match account_id {
+            2 | 3 | 7 => ("NZ", "Other"),
+            _ => ("AU", "Game"),
+        };
+
+        format!(
+            r#"<html><script>g_rgProfileData = {{"steamid":"{}"}};</script>
+<span class="actual_persona_name">P{}</span>
+<div class="header_real_name">Name, {}</div>
+<div class="game_name"><a>{}</a></div>
+</html>"#,
+            id(account_id).id64(),
+            account_id,
+            country,
+            game
+        )
+    }
+
+    fn friends_page(account_id: u32) -> String {
+        let blocks = EDGES
+            .iter()
+            .filter_map(|(a, b)| match account_id {
+                _ if *a == account_id => Some(*b),
+                _ if *b == account_id => Some(*a),
+                _ => None,
+            })
+            .map(|friend| {
+                format!(
+                    r#"<div class="selectable friend_block_v2 persona" data-steamid="{}"><a class="selectable_overlay" href="{}"></a><div class="friend_block_content">P{}<br><span>x</span></div></div>"#,
+                    id(friend).id64(),
+                    id(friend).profile_url(),
+                    friend
+                )
+            })
+            .collect::<Vec<String>>();
+
+        format!("<html>\n{}\n</html>", blocks.join("\n"))
+    }
+
+    fn graph_pages() -> MemoryFetcher {
+        let mut pages = MemoryFetcher::new();
+
+        for account_id in [1, 2, 3, 4, 5, 6, 7, 9] {
+            let url = id(account_id).profile_url();
+
+            pages.insert(format!("{}/friends/", url), friends_page(account_id));
+            pages.insert(url, profile_page(account_id));
+        }
+
+        pages
+    }
+
+    /// Counts the pages fetched through it.
+    struct CountingFetcher {
+        pages: MemoryFetcher,
+        fetched: Mutex<HashMap<String, usize>>,
+    }
+
+    impl PageFetcher for CountingFetcher {
+        fn fetch(&self, url: String) -> FetchFuture<'_> {
+            *self.fetched.lock().unwrap().entry(url.clone()).or_insert(0) += 1;
+
+            self.pages.fetch(url)
+        }
+    }
+
+    async fn search(strategy: Strategy, max_depth: usize) -> TargetResult {
+        let settings = SearchConfig {
+            strategy: strategy,
+            max_depth: max_depth,
+            ..Default::default()
+        };
+
+        search_pages(Arc::new(graph_pages()), settings).await
+    }
+
+    async fn search_pages(pages: Arc<dyn PageFetcher>, settings: SearchConfig) -> TargetResult {
+        // Saving every 0 iterations only saves when the search is quit.
+        let checkpoint = CheckpointConfig {
+            path: std::env::temp_dir().join(format!("steam_search_{}.json", std::process::id())),
+            every: 0,
+        };
+
+        let searcher = Searcher::new(
+            Arc::new(HtmlBackend::new(pages)),
+            settings,
+            ScoringWeights::default(),
+            checkpoint,
+            &id(SOURCE).profile_url(),
+            &[id(TARGET).profile_url()],
+        )
+        .await
+        .unwrap();
+
+        let (_msg_sender, msg_reciever) = tokio::sync::mpsc::channel(1);
+        let (found_sender, _found_reciever) = tokio::sync::mpsc::unbounded_channel();
+
+        let mut report = searcher
+            .start_search(msg_reciever, found_sender)
+            .await
+            .unwrap();
+
+        assert_eq!(report.results.len(), 1);
+        report.results.remove(0)
+    }
+
+    async fn assert_shortest(strategy: Strategy) {
+        let result = search(strategy, 10).await;
+
+        match result.outcome {
+            SearchOutcome::Found(path) => {
+                assert_eq!(path, vec![id(TARGET), id(3), id(2), id(SOURCE)])
+            }
+            outcome => panic!("expected a path, got {:?}", outcome),
+        }
+        assert!(result.optimal);
+    }
+
+    #[tokio::test]
+    async fn bfs_finds_the_shortest_path() {
+        assert_shortest(Strategy::Bfs).await;
+    }
+
+    #[tokio::test]
+    async fn bidirectional_finds_the_shortest_path() {
+        assert_shortest(Strategy::Bidirectional).await;
+    }
+
+    #[tokio::test]
+    async fn astar_finds_the_shortest_path_past_better_scores() {
+        assert_shortest(Strategy::AStar).await;
+    }
+
+    #[tokio::test]
+    async fn targets_beyond_max_depth_are_not_found() {
+        for strategy in [Strategy::Bfs, Strategy::Bidirectional, Strategy::AStar] {
+            let result = search(strategy, 2).await;
+
+            assert!(matches!(
+                result.outcome,
+                SearchOutcome::NotFound { max_depth: 2 }
+            ));
+        }
+    }
+
+    fn count_calls(calls: &[String], method: &str) -> usize {
+        calls.iter().filter(|call| call.as_str() == method).count()
+    }
+
+    #[tokio::test]
+    async fn web_api_scoring_fetches_only_the_shortlist_in_full() {
+        let (backend, calls) = web_api_backend().await;
+        let backend: Arc<dyn SteamBackend> = Arc::new(backend);
+
+        let accounts = (0..150)
+            .map(|index| (String::new(), SteamId::from_account_id(1000 + index)))
+            .collect();
+
+        let profiles = Arc::new(vec![AccountInfo {
+            country: String::from("AU"),
+            groups: HashSet::from([String::from("7")]),
+            recent_games: HashSet::from([String::from("Game")]),
+            ..Default::default()
+        }]);
+
+        let outcomes =
+            Searcher::score_friends(&backend, accounts, &profiles, ScoringWeights::default(), 5)
+                .await
+                .unwrap();
+
+        let scores = outcomes
+            .iter()
+            .filter_map(|outcome| match outcome {
+                ScoreOutcome::Scored(_, _, score) => Some(*score),
+                _ => None,
+            })
+            .collect::<Vec<f32>>();
+
+        assert_eq!(scores.len(), 150);
+        // Shared groups and games only show on a full profile.
+        assert_eq!(scores.iter().filter(|score| **score > 0.5_f32).count(), 5);
+
+        // Two batches of summaries for the friends and one for the shortlist, then three calls
+        // for each of the five full profiles, instead of 150 profile pages.
+        let calls = calls.lock().unwrap();
+
+        assert_eq!(count_calls(&calls, "/ISteamUser/GetPlayerSummaries/v2/"), 3);
+        assert_eq!(count_calls(&calls, "/ISteamUser/GetFriendList/v1/"), 5);
+        assert_eq!(count_calls(&calls, "/ISteamUser/GetUserGroupList/v1/"), 5);
+        assert_eq!(
+            count_calls(&calls, "/IPlayerService/GetRecentlyPlayedGames/v1/"),
+            5
+        );
+        assert_eq!(calls.len(), 18);
+    }
+
+    #[tokio::test]
+    async fn web_api_scoring_can_use_summaries_alone() {
+        let (backend, calls) = web_api_backend().await;
+        let backend: Arc<dyn SteamBackend> = Arc::new(backend);
+
+        let accounts = (0..150)
+            .map(|index| (String::new(), SteamId::from_account_id(1000 + index)))
+            .collect();
+
+        let outcomes = Searcher::score_friends(
+            &backend,
+            accounts,
+            &Arc::new(vec![AccountInfo::default()]),
+            ScoringWeights::default(),
+            0,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcomes.len(), 150);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![String::from("/ISteamUser/GetPlayerSummaries/v2/"); 2]
+        );
+    }
+
+    #[tokio::test]
+    async fn greedy_scores_each_account_once() {
+        let pages = Arc::new(CountingFetcher {
+            pages: graph_pages(),
+            fetched: Mutex::new(HashMap::new()),
+        });
+
+        let settings = SearchConfig {
+            batch_size: 1,
+            ..Default::default()
+        };
+
+        let result = search_pages(Arc::clone(&pages) as Arc<dyn PageFetcher>, settings).await;
+
+        assert!(matches!(result.outcome, SearchOutcome::Found(_)));
+
+        // Friends listed again by later expansions are already queued, so only the target,
+        // whose profile is read once up front, has its profile fetched twice.
+        for (url, count) in pages.fetched.lock().unwrap().iter() {
+            let expected = if *url == id(TARGET).profile_url() {
+                2
+            } else {
+                1
+            };
+
+            assert_eq!(*count, expected, "{}", url);
+        }
+    }
 }
@@ -0,0 +1,262 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::SteamIdError;
+
+/// SteamID64 of the first individual account in the public universe.
+const INDIVIDUAL_BASE: u64 = 76561197960265728;
+
+const PROFILE_URL_BASE: &str = "https://steamcommunity.com/profiles/";
+
+/// An individual Steam account, stored as its SteamID64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "u64", into = "u64")]
+pub struct SteamId(u64);
+
+impl SteamId {
+    pub fn from_id64(id64: u64) -> Result<Self, SteamIdError> {
+        if id64 < INDIVIDUAL_BASE || id64 - INDIVIDUAL_BASE > u32::MAX as u64 {
+            return Err(SteamIdError::Invalid(id64.to_string()));
+        }
+
+        Ok(SteamId(id64))
+    }
+
+    pub fn from_account_id(account_id: u32) -> Self {
+        SteamId(INDIVIDUAL_BASE + account_id as u64)
+    }
+
+    pub fn id64(&self) -> u64 {
+        self.0
+    }
+
+    pub fn account_id(&self) -> u32 {
+        (self.0 - INDIVIDUAL_BASE) as u32
+    }
+
+    /// Legacy `STEAM_0:Y:Z` form, where Y is the low bit of the account ID and Z the rest.
+    pub fn steam2(&self) -> String {
+        let account_id = self.account_id();
+
+        format!("STEAM_0:{}:{}", account_id & 1, account_id >> 1)
+    }
+
+    /// Modern `[U:1:W]` form, where W is the account ID.
+    pub fn steam3(&self) -> String {
+        format!("[U:1:{}]", self.account_id())
+    }
+
+    pub fn profile_url(&self) -> String {
+        format!("{}{}", PROFILE_URL_BASE, self.0)
+    }
+
+    /// Parses SteamID64, Steam2, Steam3, bare account IDs and `/profiles/` URLs. `/id/` URLs
+    /// only carry a vanity name and come back as `SteamIdError::Vanity`.
+    pub fn parse(input: &str) -> Result<Self, SteamIdError> {
+        let trimmed = input.trim();
+        let invalid = || SteamIdError::Invalid(trimmed.to_string());
+
+        if let Some(rest) = trimmed.strip_prefix("STEAM_") {
+            let parts = rest.split(':').collect::<Vec<&str>>();
+
+            if parts.len() != 3 {
+                return Err(invalid());
+            }
+
+            parts[0].parse::<u8>().map_err(|_| invalid())?;
+
+            let low_bit = parts[1].parse::<u32>().map_err(|_| invalid())?;
+            let high_bits = parts[2].parse::<u32>().map_err(|_| invalid())?;
+
+            if low_bit > 1 || high_bits > u32::MAX >> 1 {
+                return Err(invalid());
+            }
+
+            return Ok(Self::from_account_id((high_bits << 1) | low_bit));
+        }
+
+        if let Some(rest) = trimmed.strip_prefix('[') {
+            let inner = rest.strip_suffix(']').ok_or_else(invalid)?;
+            let parts = inner.split(':').collect::<Vec<&str>>();
+
+            if parts.len() != 3 || parts[0] != "U" {
+                return Err(invalid());
+            }
+
+            let account_id = parts[2].parse::<u32>().map_err(|_| invalid())?;
+
+            return Ok(Self::from_account_id(account_id));
+        }
+
+        if trimmed.contains('/') {
+            return Self::parse_url(trimmed).ok_or_else(invalid)?;
+        }
+
+        let number = trimmed.parse::<u64>().map_err(|_| invalid())?;
+
+        if number >= INDIVIDUAL_BASE {
+            Self::from_id64(number)
+        } else if number <= u32::MAX as u64 {
+            Ok(Self::from_account_id(number as u32))
+        } else {
+            Err(invalid())
+        }
+    }
+
+    fn parse_url(url: &str) -> Option<Result<Self, SteamIdError>> {
+        let segments = url.split('/').collect::<Vec<&str>>();
+        let kind_index = segments
+            .iter()
+            .position(|segment| *segment == "profiles" || *segment == "id")?;
+        let value = segments.get(kind_index + 1)?.trim();
+
+        if value.is_empty() {
+            return None;
+        }
+
+        if segments[kind_index] == "id" {
+            return Some(Err(SteamIdError::Vanity(value.to_string())));
+        }
+
+        Some(
+            value
+                .parse::<u64>()
+                .map_err(|_| SteamIdError::Invalid(url.to_string()))
+                .and_then(Self::from_id64),
+        )
+    }
+}
+
+impl FromStr for SteamId {
+    type Err = SteamIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl TryFrom<u64> for SteamId {
+    type Error = SteamIdError;
+
+    fn try_from(id64: u64) -> Result<Self, Self::Error> {
+        Self::from_id64(id64)
+    }
+}
+
+impl From<SteamId> for u64 {
+    fn from(steam_id: SteamId) -> Self {
+        steam_id.0
+    }
+}
+
+impl Display for SteamId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// STEAM_0:1:4 and [U:1:9] are both account 9.
+    const ID64: u64 = 76561197960265737;
+
+    #[test]
+    fn converts_between_formats() {
+        let steam_id = SteamId::from_id64(ID64).unwrap();
+
+        assert_eq!(steam_id.account_id(), 9);
+        assert_eq!(steam_id.steam2(), "STEAM_0:1:4");
+        assert_eq!(steam_id.steam3(), "[U:1:9]");
+        assert_eq!(
+            steam_id.profile_url(),
+            "https://steamcommunity.com/profiles/76561197960265737"
+        );
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        for account_id in [0, 1, 9, 22202, u32::MAX - 1, u32::MAX] {
+            let steam_id = SteamId::from_account_id(account_id);
+
+            for text in [
+                steam_id.id64().to_string(),
+                steam_id.steam2(),
+                steam_id.steam3(),
+                steam_id.profile_url(),
+                account_id.to_string(),
+            ] {
+                assert_eq!(SteamId::parse(&text), Ok(steam_id), "{}", text);
+            }
+
+            assert_eq!(SteamId::from_id64(steam_id.id64()), Ok(steam_id));
+        }
+    }
+
+    #[test]
+    fn parses_each_format() {
+        let steam_id = SteamId::from_account_id(9);
+
+        assert_eq!(SteamId::parse("STEAM_0:1:4"), Ok(steam_id));
+        assert_eq!(SteamId::parse("STEAM_1:1:4"), Ok(steam_id));
+        assert_eq!(SteamId::parse("[U:1:9]"), Ok(steam_id));
+        assert_eq!(SteamId::parse(" 9 "), Ok(steam_id));
+        assert_eq!(SteamId::parse("76561197960265737"), Ok(steam_id));
+        assert_eq!(
+            SteamId::parse("https://steamcommunity.com/profiles/76561197960265737/friends/"),
+            Ok(steam_id)
+        );
+    }
+
+    #[test]
+    fn vanity_urls_carry_their_name() {
+        assert_eq!(
+            SteamId::parse("https://steamcommunity.com/id/gabelogannewell/"),
+            Err(SteamIdError::Vanity(String::from("gabelogannewell")))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_ids() {
+        let below_base = (INDIVIDUAL_BASE - 1).to_string();
+
+        assert!(SteamId::from_id64(INDIVIDUAL_BASE - 1).is_err());
+        assert!(SteamId::from_id64(INDIVIDUAL_BASE + u32::MAX as u64 + 1).is_err());
+
+        for input in [
+            below_base.as_str(),
+            "",
+            "gaben",
+            "STEAM_0:2:4",
+            "STEAM_0:1",
+            "STEAM_0:1:x",
+            "[G:1:9]",
+            "[U:1:9",
+            "4294967296",
+            "https://steamcommunity.com/profiles/",
+            "https://steamcommunity.com/profiles/abc",
+            "https://steamcommunity.com/profiles/5",
+        ] {
+            assert!(
+                matches!(SteamId::parse(input), Err(SteamIdError::Invalid(_))),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn serializes_as_id64() {
+        let steam_id = SteamId::from_account_id(9);
+
+        assert_eq!(serde_json::to_string(&steam_id).unwrap(), ID64.to_string());
+        assert_eq!(
+            serde_json::from_str::<SteamId>("76561197960265737").unwrap(),
+            steam_id
+        );
+        assert!(serde_json::from_str::<SteamId>("5").is_err());
+    }
+}
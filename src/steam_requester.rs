@@ -2,14 +2,18 @@ use core::num;
 use std::default;
 use std::{error::Error, fmt::Display};
 
+use serde::{Deserialize, Serialize};
 use tl::{Node, NodeHandle, Parser};
 
-use crate::error::SteamError;
+use crate::config::ScoringWeights;
+use crate::error::{SteamError, SteamIdError};
+use crate::fetcher::PageFetcher;
+use crate::steam_id::SteamId;
 use crate::util::{combine_tuple_lists, print_and_return, round};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AccountInfo {
     pub name: String,
     pub recent_games: HashSet<String>,
@@ -18,19 +22,66 @@ pub struct AccountInfo {
     pub country: String,
     pub num_friends: f32,
     pub private: bool,
+    pub steam_id: Option<SteamId>,
 }
 
-pub async fn test_account_build_info() {
+pub async fn test_account_build_info(fetcher: &dyn PageFetcher) {
     //https://steamcommunity.com/profiles/76561198043820228
 
-    let account = build_account_info(String::from("https://steamcommunity.com/id/demizegg"))
-        .await
-        .unwrap();
+    let account = build_account_info(
+        fetcher,
+        String::from("https://steamcommunity.com/id/demizegg"),
+    )
+    .await
+    .unwrap();
 
     println!("{:?}", account);
 }
 
-pub fn score_account_overlap(base_account: &AccountInfo, scored_account: &AccountInfo) -> f32 {
+/// Merges several accounts into one profile to score against: every game and group of any of
+/// them, and the country and favorite game they have most often in common.
+pub fn combine_account_info(accounts: &[AccountInfo]) -> AccountInfo {
+    let most_common = |values: Vec<&String>| -> String {
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+
+        for value in values.into_iter().filter(|value| !value.is_empty()) {
+            *counts.entry(value).or_insert(0) += 1;
+        }
+
+        counts
+            .into_iter()
+            .max_by(|(a, count_a), (b, count_b)| count_a.cmp(count_b).then(b.cmp(a)))
+            .map(|(value, _)| value.clone())
+            .unwrap_or_default()
+    };
+
+    AccountInfo {
+        name: accounts
+            .iter()
+            .map(|account| account.name.clone())
+            .collect::<Vec<String>>()
+            .join(", "),
+        recent_games: accounts
+            .iter()
+            .flat_map(|account| account.recent_games.iter().cloned())
+            .collect(),
+        groups: accounts
+            .iter()
+            .flat_map(|account| account.groups.iter().cloned())
+            .collect(),
+        favorite_game: most_common(accounts.iter().map(|a| &a.favorite_game).collect()),
+        country: most_common(accounts.iter().map(|a| &a.country).collect()),
+        num_friends: accounts.iter().map(|a| a.num_friends).sum::<f32>() / accounts.len() as f32,
+        private: false,
+        steam_id: None,
+    }
+}
+
+pub fn score_account_overlap(
+    base_account: &AccountInfo,
+    scored_account: &AccountInfo,
+    weights: &ScoringWeights,
+) -> f32 {
     let norm_size = base_account.recent_games.len();
 
     let inter_size = base_account
@@ -65,14 +116,17 @@ pub fn score_account_overlap(base_account: &AccountInfo, scored_account: &Accoun
         0_f32
     };
 
-    let friend_score = scored_account.num_friends.log(1000_f32).clamp(0_f32, 1_f32);
+    let friend_score = scored_account
+        .num_friends
+        .log(weights.friend_count_cap as f32)
+        .clamp(0_f32, 1_f32);
 
     round(
-        0.15 * country_score
-            + 0.15 * recent_games_score
-            + 0.1 * fav_game_score
-            + 0.1 * friend_score
-            + 0.5 * groups_score,
+        weights.country as f32 * country_score
+            + weights.recent_games as f32 * recent_games_score
+            + weights.favorite_game as f32 * fav_game_score
+            + weights.friends as f32 * friend_score
+            + weights.groups as f32 * groups_score,
         4,
     )
 }
@@ -91,8 +145,13 @@ fn extract_child_text<'a>(node: &Node<'a>, parser: &Parser<'a>) -> Option<String
     Some(child_text_combined.trim().to_string())
 }
 
-pub async fn build_account_info(link: String) -> Result<AccountInfo, Box<dyn Error>> {
-    let raw_page = profile_from_link(link).await?;
+pub async fn build_account_info(
+    fetcher: &dyn PageFetcher,
+    link: String,
+) -> Result<AccountInfo, Box<dyn Error>> {
+    let raw_page = profile_from_link(fetcher, link).await?;
+
+    let steam_id = extract_embedded_steam_id(&raw_page);
 
     let parse_options = tl::ParserOptions::default();
 
@@ -108,6 +167,7 @@ pub async fn build_account_info(link: String) -> Result<AccountInfo, Box<dyn Err
     if let Some(_) = private_field {
         return Ok(AccountInfo {
             private: true,
+            steam_id: steam_id,
             ..Default::default()
         });
     }
@@ -177,11 +237,49 @@ pub async fn build_account_info(link: String) -> Result<AccountInfo, Box<dyn Err
         country: country,
         num_friends: num_friends as f32,
         private: false,
+        steam_id: steam_id,
     })
 }
 
-pub async fn get_profile_info(id: &'static str) -> Result<String, Box<dyn Error>> {
-    let raw_page = profile_from_id(id).await?;
+/// Profile pages embed the owner's SteamID64 in their inline profile data script.
+pub fn extract_embedded_steam_id(raw_page: &str) -> Option<SteamId> {
+    let marker = "\"steamid\":\"";
+    let start = raw_page.find(marker)? + marker.len();
+    let digits = raw_page[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|id| SteamId::from_id64(id).ok())
+}
+
+/// Turns any profile link into a SteamID, fetching the profile page when it only has a vanity name.
+pub async fn resolve_steam_id(
+    fetcher: &dyn PageFetcher,
+    link: String,
+) -> Result<SteamId, Box<dyn Error>> {
+    match SteamId::parse(&link) {
+        Ok(steam_id) => Ok(steam_id),
+        Err(SteamIdError::Vanity(_)) => {
+            let raw_page = profile_from_link(fetcher, link).await?;
+
+            match extract_embedded_steam_id(&raw_page) {
+                Some(steam_id) => Ok(steam_id),
+                None => Err(SteamError::boxed_new("Profile page has no SteamID")),
+            }
+        }
+        Err(err) => Err(Box::new(err)),
+    }
+}
+
+pub async fn get_profile_info(
+    fetcher: &dyn PageFetcher,
+    id: SteamId,
+) -> Result<String, Box<dyn Error>> {
+    let raw_page = profile_from_id(fetcher, id).await?;
 
     let parse_options = tl::ParserOptions::default();
 
@@ -216,8 +314,33 @@ pub fn get_href_from_node<'a>(node: &Node<'a>) -> Option<String> {
     Some(link.as_utf8_str().to_string())
 }
 
-pub async fn get_friends(link: String) -> Result<Vec<(String, String)>, Box<dyn Error>> {
-    let raw_friends = raw_friends_page(link + "/friends/").await?;
+pub fn get_steam_id_from_node<'a>(node: &Node<'a>) -> Option<SteamId> {
+    let tag = node.as_tag()?;
+
+    let attributes = tag.attributes();
+
+    if let Some(Some(id64)) = attributes.get("data-steamid") {
+        if let Ok(steam_id) = SteamId::parse(&id64.as_utf8_str()) {
+            return Some(steam_id);
+        }
+    }
+
+    let account_id = attributes.get("data-miniprofile")??;
+
+    account_id
+        .as_utf8_str()
+        .parse::<u32>()
+        .ok()
+        .map(SteamId::from_account_id)
+}
+
+/// Friends as `(name, steam_id, link)`. The SteamID is `None` for the rare friend block that
+/// carries no ID and only links a vanity URL, which the caller has to resolve.
+pub async fn get_friends(
+    fetcher: &dyn PageFetcher,
+    link: String,
+) -> Result<Vec<(String, Option<SteamId>, String)>, Box<dyn Error>> {
+    let raw_friends = raw_friends_page(fetcher, link + "/friends/").await?;
 
     let parse_options = tl::ParserOptions::default();
 
@@ -238,27 +361,151 @@ pub async fn get_friends(link: String) -> Result<Vec<(String, String)>, Box<dyn
         .filter_map(|content| extract_friend_name(content))
         .collect::<Vec<String>>();
 
-    Ok(combine_tuple_lists(friend_names, friend_links))
+    let block_ids = dom
+        .get_elements_by_class_name("friend_block_v2")
+        .filter_map(|node| node.get(parser))
+        .map(get_steam_id_from_node)
+        .collect::<Vec<Option<SteamId>>>();
+
+    let block_ids = if block_ids.len() == friend_links.len() {
+        block_ids
+    } else {
+        vec![None; friend_links.len()]
+    };
+
+    // Blocks normally carry their SteamID; fall back to the link, which only works for
+    // `/profiles/` links.
+    let friend_ids = combine_tuple_lists(block_ids, friend_links)
+        .into_iter()
+        .map(|(steam_id, link)| (steam_id.or(SteamId::parse(&link).ok()), link))
+        .collect::<Vec<(Option<SteamId>, String)>>();
+
+    Ok(combine_tuple_lists(friend_names, friend_ids)
+        .into_iter()
+        .map(|(name, (steam_id, link))| (name, steam_id, link))
+        .collect())
 }
 
 pub fn extract_friend_name<'a>(content: String) -> Option<String> {
     content.split('<').next().map(|str| str.trim().to_string())
 }
 
-pub async fn raw_friends_page(link: String) -> Result<String, Box<dyn Error>> {
-    let friends_page = reqwest::get(link).await?.text().await?;
+pub async fn raw_friends_page(
+    fetcher: &dyn PageFetcher,
+    link: String,
+) -> Result<String, Box<dyn Error>> {
+    let friends_page = fetcher.fetch(link).await?;
 
     Ok(friends_page)
 }
 
-async fn profile_from_link(link: String) -> Result<String, Box<dyn Error>> {
-    let raw_page = reqwest::get(link).await?.text().await?;
+async fn profile_from_link(
+    fetcher: &dyn PageFetcher,
+    link: String,
+) -> Result<String, Box<dyn Error>> {
+    let raw_page = fetcher.fetch(link).await?;
 
     Ok(raw_page)
 }
 
-pub async fn profile_from_id(id: &'static str) -> Result<String, Box<dyn Error>> {
-    let profile_link = format!("https://steamcommunity.com/profiles/{}", id);
+pub async fn profile_from_id(
+    fetcher: &dyn PageFetcher,
+    id: SteamId,
+) -> Result<String, Box<dyn Error>> {
+    profile_from_link(fetcher, id.profile_url()).await
+}
 
-    profile_from_link(profile_link).await
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::error::FetchError;
+    use crate::fetcher::MemoryFetcher;
+
+    const PROFILE: &str = "https://steamcommunity.com/profiles/76561197960265729";
+
+    const PROFILE_PAGE: &str = r#"<html><script>g_rgProfileData = {"steamid":"76561197960265729"};</script>
+<span class="actual_persona_name">P29</span><div class="header_real_name"><bdi></bdi> Sydney, AU</div>
+<div class="game_name"><a>Game A</a></div><div class="game_name"><a>Game B</a></div>
+<div class="profile_group"><div></div><div></div><div></div><div></div><div></div><div></div><div></div><div></div><div></div><a><span>G1</span></a></div>
+</html>"#;
+
+    const FRIENDS_PAGE: &str = r#"<html>
+<div class="selectable friend_block_v2 persona" data-steamid="76561197960265730"><a class="selectable_overlay" href="https://steamcommunity.com/profiles/76561197960265730"></a><div class="friend_block_content">P30<br><span>x</span></div></div>
+<div class="selectable friend_block_v2 persona" data-miniprofile="3"><a class="selectable_overlay" href="https://steamcommunity.com/id/vanity"></a><div class="friend_block_content">P31<br><span>x</span></div></div>
+</html>"#;
+
+    fn fetcher() -> MemoryFetcher {
+        MemoryFetcher::from_pages(HashMap::from([
+            (PROFILE.to_string(), PROFILE_PAGE.to_string()),
+            (format!("{}/friends/", PROFILE), FRIENDS_PAGE.to_string()),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn builds_account_info_from_recorded_page() {
+        let account = build_account_info(&fetcher(), PROFILE.to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(account.name, "P29");
+        assert_eq!(account.country, "AU");
+        assert_eq!(
+            account.recent_games,
+            HashSet::from(["Game A".to_string(), "Game B".to_string()])
+        );
+        assert_eq!(account.groups, HashSet::from(["G1".to_string()]));
+        assert_eq!(account.steam_id, Some(SteamId::from_account_id(1)));
+        assert!(!account.private);
+    }
+
+    #[tokio::test]
+    async fn lists_friends_from_recorded_page() {
+        let friends = get_friends(&fetcher(), PROFILE.to_string()).await.unwrap();
+
+        assert_eq!(
+            friends,
+            vec![
+                (
+                    "P30".to_string(),
+                    Some(SteamId::from_account_id(2)),
+                    "https://steamcommunity.com/profiles/76561197960265730".to_string()
+                ),
+                (
+                    "P31".to_string(),
+                    Some(SteamId::from_account_id(3)),
+                    "https://steamcommunity.com/id/vanity".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn private_profile_has_no_details() {
+        let mut fetcher = MemoryFetcher::new();
+        fetcher.insert(
+            PROFILE.to_string(),
+            r#"<html><div class="profile_private_info">Private</div></html>"#.to_string(),
+        );
+
+        let account = build_account_info(&fetcher, PROFILE.to_string())
+            .await
+            .unwrap();
+
+        assert!(account.private);
+        assert!(account.name.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_page_is_an_error() {
+        let err = get_friends(&MemoryFetcher::new(), PROFILE.to_string())
+            .await
+            .unwrap_err();
+
+        match err.downcast_ref::<FetchError>() {
+            Some(FetchError::MissingPage(url)) => assert_eq!(url, &format!("{}/friends/", PROFILE)),
+            other => panic!("expected a missing page, got {:?}", other),
+        }
+    }
 }
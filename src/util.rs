@@ -1,6 +1,7 @@
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::hash::Hash;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::time::Instant;
 
 pub fn combine_tuple_lists<T: Clone, U: Clone>(a: Vec<T>, b: Vec<U>) -> Vec<(T, U)> {
@@ -10,10 +11,6 @@ pub fn combine_tuple_lists<T: Clone, U: Clone>(a: Vec<T>, b: Vec<U>) -> Vec<(T,
         .collect::<Vec<(T, U)>>()
 }
 
-pub fn unzip_tuple_lists<T: Clone, U: Clone>(a: Vec<(T, U)>) -> (Vec<T>, Vec<U>) {
-    a.clone().into_iter().unzip()
-}
-
 pub fn join_maps_on_shortest<T: Clone + Eq + Hash>(
     a: &HashMap<T, Vec<T>>,
     b: &HashMap<T, Vec<T>>,
@@ -37,14 +34,18 @@ pub fn maps_have_intersection<T: Clone + Eq + Hash, U: Clone>(
     a: &HashMap<T, U>,
     b: &HashMap<T, U>,
 ) -> bool {
-    a.clone()
-        .into_keys()
-        .filter(|val| b.contains_key(val))
-        .next()
-        != None
+    maps_intersection(a, b).next().is_some()
+}
+
+/// Keys present in both maps, visiting the keys of `a`.
+pub fn maps_intersection<'a, T: Eq + Hash, U, V>(
+    a: &'a HashMap<T, U>,
+    b: &'a HashMap<T, V>,
+) -> impl Iterator<Item = &'a T> {
+    a.keys().filter(|key| b.contains_key(*key))
 }
 
-pub fn time_fn<T, F: Fn() -> T>(function: F, title: &'static str) -> T {
+pub fn time_fn<T, F: FnOnce() -> T>(function: F, title: &'static str) -> T {
     println!("Starting: {}", title);
 
     let start = Instant::now();
@@ -80,3 +81,10 @@ pub fn print_and_return<T: Debug>(val: T) -> T {
     println!("{:?}", val);
     val
 }
+
+/// Uniform value in [0, 1), good enough for jitter without pulling in an RNG crate.
+pub fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+
+    (bits >> 11) as f64 / (1_u64 << 53) as f64
+}
@@ -0,0 +1,716 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout_at, Instant};
+
+use crate::backend::{BackendFuture, FriendList, SteamBackend};
+use crate::error::{FetchError, SteamError, SteamIdError};
+use crate::fetcher::PageFetcher;
+use crate::resolver::ResolutionCache;
+use crate::steam_id::SteamId;
+use crate::steam_requester::AccountInfo;
+
+pub const DEFAULT_API_BASE_URL: &str = "https://api.steampowered.com";
+
+const SUMMARIES_PER_REQUEST: usize = 100;
+const PUBLIC_VISIBILITY: u32 = 3;
+
+#[derive(Debug, Deserialize)]
+struct ResponseWrapper<T> {
+    response: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct VanityResponse {
+    steamid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FriendListWrapper {
+    friendslist: FriendsList,
+}
+
+#[derive(Debug, Deserialize)]
+struct FriendsList {
+    #[serde(default)]
+    friends: Vec<Friend>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Friend {
+    steamid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlayerSummaries {
+    #[serde(default)]
+    players: Vec<PlayerSummary>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PlayerSummary {
+    steamid: String,
+    #[serde(default)]
+    personaname: String,
+    #[serde(default)]
+    communityvisibilitystate: u32,
+    #[serde(default)]
+    loccountrycode: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroupList {
+    #[serde(default)]
+    groups: Vec<Group>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Group {
+    gid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentGames {
+    #[serde(default)]
+    games: Vec<RecentGame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecentGame {
+    #[serde(default)]
+    name: String,
+}
+
+/// How long a summary lookup waits for others to share its `GetPlayerSummaries` call.
+const SUMMARY_BATCH_WINDOW: Duration = Duration::from_millis(25);
+
+struct ApiClient {
+    fetcher: Arc<dyn PageFetcher>,
+    api_key: String,
+    base_url: String,
+}
+
+impl ApiClient {
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T, Box<dyn Error>> {
+        let mut query = vec![("key", self.api_key.as_str())];
+        query.extend_from_slice(params);
+
+        let url = reqwest::Url::parse_with_params(
+            format!("{}/{}", self.base_url, method).as_str(),
+            &query,
+        )?;
+
+        let body = self.fetcher.fetch(url.to_string()).await?;
+
+        Ok(serde_json::from_str::<T>(body.as_str())?)
+    }
+
+    async fn player_summaries(
+        &self,
+        steam_ids: &[String],
+    ) -> Result<Vec<PlayerSummary>, Box<dyn Error>> {
+        let joined = steam_ids.join(",");
+
+        let summaries: ResponseWrapper<PlayerSummaries> = self
+            .call(
+                "ISteamUser/GetPlayerSummaries/v2/",
+                &[("steamids", joined.as_str())],
+            )
+            .await?;
+
+        Ok(summaries.response.players)
+    }
+}
+
+/// The summary, or the error the shared call failed with, so every waiter can tell whether it is
+/// fatal or worth retrying.
+type SummaryReply = Result<Option<PlayerSummary>, Arc<FetchError>>;
+
+struct SummaryRequest {
+    steam_id: String,
+    reply: oneshot::Sender<SummaryReply>,
+}
+
+/// Coalesces summary lookups arriving within `SUMMARY_BATCH_WINDOW` into shared calls of up to
+/// `SUMMARIES_PER_REQUEST` ids, so scoring a whole friend list costs a handful of requests.
+async fn run_summary_batches(client: Arc<ApiClient>, mut requests: mpsc::Receiver<SummaryRequest>) {
+    while let Some(first) = requests.recv().await {
+        let mut batch = vec![first];
+        let deadline = Instant::now() + SUMMARY_BATCH_WINDOW;
+
+        while batch.len() < SUMMARIES_PER_REQUEST {
+            match timeout_at(deadline, requests.recv()).await {
+                Ok(Some(request)) => batch.push(request),
+                _ => break,
+            }
+        }
+
+        let client_ref = Arc::clone(&client);
+
+        tokio::spawn(async move { answer_summary_batch(&client_ref, batch).await });
+    }
+}
+
+async fn answer_summary_batch(client: &ApiClient, batch: Vec<SummaryRequest>) {
+    let mut steam_ids = batch
+        .iter()
+        .map(|request| request.steam_id.clone())
+        .collect::<Vec<String>>();
+
+    steam_ids.sort();
+    steam_ids.dedup();
+
+    let summaries = client
+        .player_summaries(&steam_ids)
+        .await
+        .map(|players| {
+            players
+                .into_iter()
+                .map(|summary| (summary.steamid.clone(), summary))
+                .collect::<HashMap<String, PlayerSummary>>()
+        })
+        .map_err(|err| {
+            Arc::new(match err.downcast::<FetchError>() {
+                Ok(fetch_err) => *fetch_err,
+                Err(err) => FetchError::Malformed(err.to_string()),
+            })
+        });
+
+    for request in batch {
+        let reply = match &summaries {
+            Ok(summaries) => Ok(summaries.get(&request.steam_id).cloned()),
+            Err(err) => Err(Arc::clone(err)),
+        };
+
+        // The caller may have given up waiting, which is fine.
+        let _ = request.reply.send(reply);
+    }
+}
+
+/// Reads accounts through the official Steam Web API instead of scraping profile pages.
+pub struct WebApiBackend {
+    client: Arc<ApiClient>,
+    summary_requests: mpsc::Sender<SummaryRequest>,
+    resolved: ResolutionCache,
+}
+
+impl WebApiBackend {
+    /// Points the backend at another server, such as a local stand-in serving canned JSON.
+    pub fn with_base_url(fetcher: Arc<dyn PageFetcher>, api_key: String, base_url: String) -> Self {
+        let client = Arc::new(ApiClient {
+            fetcher: fetcher,
+            api_key: api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        });
+
+        let (sender, reciever) = mpsc::channel::<SummaryRequest>(SUMMARIES_PER_REQUEST * 10);
+
+        tokio::spawn(run_summary_batches(Arc::clone(&client), reciever));
+
+        WebApiBackend {
+            client: client,
+            summary_requests: sender,
+            resolved: ResolutionCache::new(),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &[(&str, &str)],
+    ) -> Result<T, Box<dyn Error>> {
+        self.client.call(method, params).await
+    }
+
+    async fn resolve_link(&self, link: String) -> Result<SteamId, Box<dyn Error>> {
+        self.resolved
+            .resolve(&link, |canonical| self.resolve_vanity(canonical))
+            .await
+    }
+
+    async fn resolve_vanity(&self, link: String) -> Result<SteamId, Box<dyn Error>> {
+        let vanity = match SteamId::parse(&link) {
+            Ok(steam_id) => return Ok(steam_id),
+            Err(SteamIdError::Vanity(vanity)) => vanity,
+            Err(err) => return Err(Box::new(err)),
+        };
+
+        let resolved: ResponseWrapper<VanityResponse> = self
+            .call(
+                "ISteamUser/ResolveVanityURL/v1/",
+                &[("vanityurl", vanity.as_str())],
+            )
+            .await?;
+
+        match resolved.response.steamid {
+            Some(steam_id) => Ok(SteamId::parse(&steam_id)?),
+            None => Err(SteamError::boxed_new("Vanity URL could not be resolved")),
+        }
+    }
+
+    async fn friend_ids(&self, steam_id: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let friends: FriendListWrapper = self
+            .call(
+                "ISteamUser/GetFriendList/v1/",
+                &[("steamid", steam_id), ("relationship", "friend")],
+            )
+            .await?;
+
+        Ok(friends
+            .friendslist
+            .friends
+            .into_iter()
+            .map(|friend| friend.steamid)
+            .collect())
+    }
+
+    async fn request_summary(
+        &self,
+        steam_id: String,
+    ) -> Result<oneshot::Receiver<SummaryReply>, Box<dyn Error>> {
+        let (reply, response) = oneshot::channel::<SummaryReply>();
+
+        let request = SummaryRequest {
+            steam_id: steam_id,
+            reply: reply,
+        };
+
+        if self.summary_requests.send(request).await.is_err() {
+            return Err(SteamError::boxed_new("Summary batcher has stopped"));
+        }
+
+        Ok(response)
+    }
+
+    async fn await_summary(
+        response: oneshot::Receiver<SummaryReply>,
+    ) -> Result<Option<PlayerSummary>, Box<dyn Error>> {
+        match response.await? {
+            Ok(summary) => Ok(summary),
+            Err(err) => Err(Box::new(FetchError::Batch(err))),
+        }
+    }
+
+    async fn summary(&self, steam_id: String) -> Result<Option<PlayerSummary>, Box<dyn Error>> {
+        let response = self.request_summary(steam_id).await?;
+
+        Self::await_summary(response).await
+    }
+
+    /// Queues every id before waiting on any, so they can all share batched calls.
+    async fn summaries(&self, steam_ids: &[String]) -> Result<Vec<PlayerSummary>, Box<dyn Error>> {
+        let mut responses = Vec::new();
+
+        for steam_id in steam_ids {
+            responses.push(self.request_summary(steam_id.clone()).await?);
+        }
+
+        let mut players = Vec::new();
+
+        for response in responses {
+            if let Some(summary) = Self::await_summary(response).await? {
+                players.push(summary);
+            }
+        }
+
+        Ok(players)
+    }
+
+    /// Account details from its summary alone, which shares a call with up to 99 other accounts.
+    async fn build_account_summary(&self, id: SteamId) -> Result<AccountInfo, Box<dyn Error>> {
+        let summary = match self.summary(id.to_string()).await? {
+            Some(summary) => summary,
+            None => return Err(SteamError::boxed_new("Player summary not found")),
+        };
+
+        Ok(AccountInfo {
+            name: summary.personaname,
+            country: summary.loccountrycode,
+            private: summary.communityvisibilitystate != PUBLIC_VISIBILITY,
+            steam_id: Some(id),
+            ..Default::default()
+        })
+    }
+
+    async fn build_account_info(&self, id: SteamId) -> Result<AccountInfo, Box<dyn Error>> {
+        let steam_id = id.to_string();
+
+        let summary = match self.summary(steam_id.clone()).await? {
+            Some(summary) => summary,
+            None => return Err(SteamError::boxed_new("Player summary not found")),
+        };
+
+        if summary.communityvisibilitystate != PUBLIC_VISIBILITY {
+            return Ok(AccountInfo {
+                private: true,
+                steam_id: Some(id),
+                ..Default::default()
+            });
+        }
+
+        // Friend lists can be hidden on otherwise public profiles, which the API reports as an error.
+        let num_friends = self
+            .friend_ids(&steam_id)
+            .await
+            .map(|friends| friends.len())
+            .unwrap_or(0);
+
+        let groups: ResponseWrapper<GroupList> = self
+            .call(
+                "ISteamUser/GetUserGroupList/v1/",
+                &[("steamid", steam_id.as_str())],
+            )
+            .await?;
+
+        let recent_games: ResponseWrapper<RecentGames> = self
+            .call(
+                "IPlayerService/GetRecentlyPlayedGames/v1/",
+                &[("steamid", steam_id.as_str())],
+            )
+            .await?;
+
+        Ok(AccountInfo {
+            name: summary.personaname,
+            recent_games: recent_games
+                .response
+                .games
+                .into_iter()
+                .map(|game| game.name)
+                .collect::<HashSet<String>>(),
+            groups: groups
+                .response
+                .groups
+                .into_iter()
+                .map(|group| group.gid)
+                .collect::<HashSet<String>>(),
+            favorite_game: String::new(),
+            country: summary.loccountrycode,
+            num_friends: num_friends as f32,
+            private: false,
+            steam_id: Some(id),
+        })
+    }
+
+    /// Friends always come with their SteamIDs, so none are ever unresolved.
+    async fn get_friends(&self, id: SteamId) -> Result<FriendList, Box<dyn Error>> {
+        let friend_ids = self.friend_ids(&id.to_string()).await?;
+
+        let summaries = self
+            .summaries(&friend_ids)
+            .await?
+            .into_iter()
+            .map(|summary| (summary.steamid.clone(), summary))
+            .collect::<HashMap<String, PlayerSummary>>();
+
+        // Keep the friend list's order, which the summaries endpoint does not preserve.
+        let friends = friend_ids
+            .iter()
+            .filter_map(|id| summaries.get(id))
+            .filter_map(|summary| {
+                SteamId::parse(&summary.steamid)
+                    .ok()
+                    .map(|steam_id| (summary.personaname.clone(), steam_id))
+            })
+            .collect();
+
+        Ok(FriendList {
+            friends: friends,
+            unresolved: Vec::new(),
+        })
+    }
+}
+
+impl SteamBackend for WebApiBackend {
+    fn resolve(&self, link: String) -> BackendFuture<'_, SteamId> {
+        Box::pin(self.resolve_link(link))
+    }
+
+    fn account_info(&self, steam_id: SteamId) -> BackendFuture<'_, AccountInfo> {
+        Box::pin(self.build_account_info(steam_id))
+    }
+
+    fn has_summaries(&self) -> bool {
+        true
+    }
+
+    fn account_summary(&self, steam_id: SteamId) -> BackendFuture<'_, AccountInfo> {
+        Box::pin(self.build_account_summary(steam_id))
+    }
+
+    fn friends(&self, steam_id: SteamId) -> BackendFuture<'_, FriendList> {
+        Box::pin(self.get_friends(steam_id))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::error::failure_reason;
+    use crate::fetcher::ReqwestFetcher;
+    use crate::fixtures::ReplayFetcher;
+
+    const OWNER: &str = "76561197960265729";
+    /// Public, but with its friend list hidden.
+    const FRIEND: &str = "76561197960265730";
+    const PRIVATE: &str = "76561197960265731";
+
+    /// Canned reply to one Web API call, as an HTTP status line and JSON body.
+    fn canned_response(method: &str, params: &HashMap<String, String>) -> (&'static str, String) {
+        let steam_id = params.get("steamid").map(String::as_str);
+
+        let body = match method {
+            "/ISteamUser/ResolveVanityURL/v1/" => match params["vanityurl"].as_str() {
+                "owner" => format!(r#"{{"response":{{"steamid":"{}","success":1}}}}"#, OWNER),
+                _ => String::from(r#"{"response":{"success":42,"message":"No match"}}"#),
+            },
+            "/ISteamUser/GetFriendList/v1/" if steam_id == Some(OWNER) => format!(
+                r#"{{"friendslist":{{"friends":[{{"steamid":"{}"}},{{"steamid":"{}"}}]}}}}"#,
+                FRIEND, PRIVATE
+            ),
+            "/ISteamUser/GetFriendList/v1/" => return ("401 Unauthorized", String::new()),
+            "/ISteamUser/GetPlayerSummaries/v2/" => {
+                let players = params["steamids"]
+                    .split(',')
+                    .map(|id| {
+                        let visibility = if id == PRIVATE { 1 } else { 3 };
+
+                        format!(
+                            r#"{{"steamid":"{}","personaname":"p{}","communityvisibilitystate":{},"loccountrycode":"AU"}}"#,
+                            id,
+                            &id[id.len() - 2..],
+                            visibility
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",");
+
+                format!(r#"{{"response":{{"players":[{}]}}}}"#, players)
+            }
+            "/ISteamUser/GetUserGroupList/v1/" => {
+                String::from(r#"{"response":{"success":true,"groups":[{"gid":"7"},{"gid":"9"}]}}"#)
+            }
+            "/IPlayerService/GetRecentlyPlayedGames/v1/" => String::from(
+                r#"{"response":{"total_count":1,"games":[{"appid":1,"name":"Game"}]}}"#,
+            ),
+            _ => return ("404 Not Found", String::new()),
+        };
+
+        ("200 OK", body)
+    }
+
+    /// Local stand-in for api.steampowered.com. Returns its base URL and the methods called.
+    async fn stand_in_server() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let calls_ref = Arc::clone(&calls);
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let calls = Arc::clone(&calls_ref);
+
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 4096];
+
+                    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                        match stream.read(&mut buffer).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => request.extend_from_slice(&buffer[..read]),
+                        }
+                    }
+
+                    let request = String::from_utf8_lossy(&request).to_string();
+                    let target = request.split_whitespace().nth(1).unwrap_or("/");
+                    let url = reqwest::Url::parse(&format!("http://stand-in{}", target)).unwrap();
+                    let params = url.query_pairs().into_owned().collect();
+
+                    calls.lock().unwrap().push(url.path().to_string());
+
+                    let (status, body) = canned_response(url.path(), &params);
+
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        (format!("http://{}/", address), calls)
+    }
+
+    /// Backend talking to a fresh stand-in server, and the methods called on it so far.
+    pub(crate) async fn backend() -> (WebApiBackend, Arc<Mutex<Vec<String>>>) {
+        let (base_url, calls) = stand_in_server().await;
+        let backend = WebApiBackend::with_base_url(
+            Arc::new(ReqwestFetcher::new()),
+            String::from("KEY"),
+            base_url,
+        );
+
+        (backend, calls)
+    }
+
+    fn id(steam_id: &str) -> SteamId {
+        SteamId::parse(steam_id).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_vanity_links() {
+        let (backend, _) = backend().await;
+
+        let resolved = backend
+            .resolve(String::from("https://steamcommunity.com/id/owner/"))
+            .await
+            .unwrap();
+
+        assert_eq!(resolved, id(OWNER));
+        assert!(backend
+            .resolve(String::from("https://steamcommunity.com/id/nobody/"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn lists_friends_with_their_names() {
+        let (backend, _) = backend().await;
+
+        let friend_list = backend.friends(id(OWNER)).await.unwrap();
+
+        assert!(friend_list.unresolved.is_empty());
+        assert_eq!(
+            friend_list.friends,
+            vec![
+                (String::from("p30"), id(FRIEND)),
+                (String::from("p31"), id(PRIVATE))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn hidden_friend_list_is_an_error() {
+        let (backend, _) = backend().await;
+
+        let err = backend.friends(id(FRIEND)).await.unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<FetchError>(),
+            Some(FetchError::Status { code: 401, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn builds_account_info_from_every_endpoint() {
+        let (backend, _) = backend().await;
+
+        let account = backend.account_info(id(OWNER)).await.unwrap();
+
+        assert_eq!(account.name, "p29");
+        assert_eq!(account.country, "AU");
+        assert_eq!(account.num_friends, 2_f32);
+        assert_eq!(
+            account.groups,
+            HashSet::from([String::from("7"), String::from("9")])
+        );
+        assert_eq!(account.recent_games, HashSet::from([String::from("Game")]));
+        assert_eq!(account.steam_id, Some(id(OWNER)));
+        assert!(!account.private);
+    }
+
+    #[tokio::test]
+    async fn hidden_friend_list_counts_no_friends() {
+        let (backend, _) = backend().await;
+
+        let account = backend.account_info(id(FRIEND)).await.unwrap();
+
+        assert_eq!(account.num_friends, 0_f32);
+        assert!(!account.private);
+    }
+
+    #[tokio::test]
+    async fn private_profiles_have_no_details() {
+        let (backend, calls) = backend().await;
+
+        let account = backend.account_info(id(PRIVATE)).await.unwrap();
+
+        assert!(account.private);
+        assert!(account.groups.is_empty());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![String::from("/ISteamUser/GetPlayerSummaries/v2/")]
+        );
+    }
+
+    #[tokio::test]
+    async fn friend_summaries_share_one_call() {
+        let (backend, calls) = backend().await;
+
+        backend.friends(id(OWNER)).await.unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                String::from("/ISteamUser/GetFriendList/v1/"),
+                String::from("/ISteamUser/GetPlayerSummaries/v2/")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn summaries_skip_the_other_endpoints() {
+        let (backend, calls) = backend().await;
+
+        let (public, private) = tokio::join!(
+            backend.account_summary(id(OWNER)),
+            backend.account_summary(id(PRIVATE))
+        );
+        let public = public.unwrap();
+
+        assert_eq!(public.name, "p29");
+        assert_eq!(public.country, "AU");
+        assert!(public.groups.is_empty());
+        assert!(!public.private);
+        assert!(private.unwrap().private);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![String::from("/ISteamUser/GetPlayerSummaries/v2/")]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_failures_keep_their_fatality() {
+        let dir = std::env::temp_dir().join(format!("steam_no_fixtures_{}", std::process::id()));
+        let backend = WebApiBackend::with_base_url(
+            Arc::new(ReplayFetcher::new(dir)),
+            String::from("KEY"),
+            String::from(DEFAULT_API_BASE_URL),
+        );
+
+        let err = backend.account_info(id(OWNER)).await.unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<FetchError>(),
+            Some(FetchError::Batch(inner)) if matches!(**inner, FetchError::MissingFixture(_))
+        ));
+        assert!(failure_reason(err).is_err());
+    }
+}